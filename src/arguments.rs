@@ -1,11 +1,18 @@
+use std::path::PathBuf;
+
 use clap::{
-    Args, Parser, Subcommand,
+    Args, Parser, Subcommand, ValueEnum,
     builder::{
         Styles,
         styling::{AnsiColor, Effects},
     },
     crate_authors, crate_description, crate_version,
 };
+use clap_complete::Shell;
+
+use crate::display_control::ColorMode;
+use crate::program::{ConflictPolicy, MetadataKey};
+use crate::shell::ShellType;
 
 // Configures Clap v3-style help menu colors
 const STYLES: Styles = Styles::styled()
@@ -19,6 +26,37 @@ const STYLES: Styles = Styles::styled()
 #[command(about = crate_description!())]
 #[command(styles = STYLES)]
 pub struct Arguments {
+    /// Keep programs, bin entries and settings in this directory instead of `~/.spm`,
+    /// overriding `SPM_HOME`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub home: Option<PathBuf>,
+    /// Only print errors, warnings and the output that was asked for
+    #[arg(short, long, global = true, default_value_t = false, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also print the paths, URLs and commands spm works with
+    #[arg(long, global = true, default_value_t = false)]
+    pub verbose: bool,
+    /// When to color the output
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+    /// Never color the output, the same as `--color never`
+    #[arg(long, global = true, default_value_t = false)]
+    pub no_color: bool,
+    /// Use plain numbered prompts instead of arrow-key selection lists
+    #[arg(long, global = true, default_value_t = false)]
+    pub no_fancy: bool,
+    /// Write progress events as JSON lines to this file, e.g. `/dev/fd/3`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub progress_file: Option<PathBuf>,
+    /// Install and run scripts whose interpreter is missing, running them with sh
+    #[arg(long, global = true, default_value_t = false)]
+    pub ignore_interpreter: bool,
+    /// Never use the network, installing git repositories from the cache only
+    #[arg(long, global = true, default_value_t = false)]
+    pub offline: bool,
+    /// Wait for other spm processes to finish instead of failing
+    #[arg(long, global = true, default_value_t = false)]
+    pub wait: bool,
     /// Groupped features provided by `spm`
     #[clap(subcommand)]
     pub commands: Commands,
@@ -32,6 +70,14 @@ pub enum Commands {
     Install(InstallArguments),
     /// Show installed shell script programs
     List(ListArguments),
+    /// Search installed programs by keywords
+    Search(SearchArguments),
+    /// Show the details of an installed program
+    Info(InfoArguments),
+    /// Edit the metadata of an installed program
+    Meta(MetaArguments),
+    /// Update installed programs from where they were installed
+    Update(UpdateArguments),
     /// Uninstall shell script programs
     #[clap(short_flag = 'r')]
     Uninstall(UninstallArguments),
@@ -39,11 +85,58 @@ pub enum Commands {
     Check(CheckArguments),
     /// Create a new shell script program
     New(NewArguments),
+    /// Regenerate the bin entries of installed programs
+    Relink(RelinkArguments),
+    /// Generate shell completions, or install them for your shell
+    Completions(CompletionsArguments),
+    /// Run an installed program on a cron schedule
+    Schedule(ScheduleArguments),
+    /// Manage the registries that programs can be installed from by name
+    Registry(RegistryArguments),
+    /// Remove leftover temporary files, and optionally the cache
+    Clean(CleanArguments),
+    /// Explain an error code, or list all of them
+    Explain(ExplainArguments),
     /// Check version info
     #[clap(short_flag = 'v')]
     Version(VersionArguments),
 }
 
+impl Commands {
+    /// Whether the command reads or changes the installed programs,
+    /// which requires spm's directories to be set up
+    pub fn uses_programs(&self) -> bool {
+        !matches!(
+            self,
+            Commands::New(_)
+                | Commands::Completions(_)
+                | Commands::Registry(_)
+                | Commands::Clean(_)
+                | Commands::Explain(_)
+                | Commands::Version(_)
+        )
+    }
+
+    /// Whether the command changes the installed programs, or the files used
+    /// while changing them, which only one process may do at a time
+    pub fn changes_programs(&self) -> bool {
+        matches!(
+            self,
+            Commands::Install(_)
+                | Commands::Update(_)
+                | Commands::Uninstall(_)
+                | Commands::Meta(_)
+                | Commands::Relink(_)
+                | Commands::Clean(_)
+        )
+    }
+
+    /// Whether the command manages the bin entries of programs
+    pub fn uses_bin_directory(&self) -> bool {
+        matches!(self, Commands::Install(_) | Commands::Relink(_))
+    }
+}
+
 #[derive(Debug, Args)]
 #[command(group = clap::ArgGroup::new("sources").required(false).multiple(false))]
 pub struct RunArguments {
@@ -53,6 +146,14 @@ pub struct RunArguments {
     #[arg(group = "sources", default_value = ".")]
     pub expression: String,
 
+    /// Do not apply the default arguments configured for the program
+    #[arg(long, default_value_t = false)]
+    pub no_default_args: bool,
+
+    /// Open an interactive shell with the environment of the program instead of running it
+    #[arg(long, conflicts_with = "args", default_value_t = false)]
+    pub shell: bool,
+
     /// Additional arguments to pass to the shell script
     #[arg(trailing_var_arg = true)]
     pub args: Vec<String>,
@@ -67,6 +168,41 @@ pub struct InstallArguments {
     /// Force to install the program, or perform an update. Use `-F` for short.
     #[arg(short = 'F', long, group = "sources", default_value_t = false)]
     pub force: bool,
+    /// Install a git repository at a tag, branch or commit.
+    /// `<url>@<ref>` in the path does the same.
+    #[arg(long = "version", value_name = "REF")]
+    pub git_reference: Option<String>,
+    /// Treat the url as a single script file instead of a git repository.
+    /// Urls ending with `.sh` are treated this way automatically.
+    #[arg(long, default_value_t = false)]
+    pub single_file: bool,
+    /// Clone the whole history of a git repository instead of only the
+    /// commit being installed
+    #[arg(long, default_value_t = false)]
+    pub full_clone: bool,
+    /// Only install the scripts of this directory of a git repository.
+    /// `<url>//<directory>` in the path does the same.
+    #[arg(long, value_name = "DIRECTORY")]
+    pub subdir: Option<String>,
+    /// Install a downloaded script under this name instead of the one in the url
+    #[arg(long = "as", value_name = "NAME")]
+    pub as_name: Option<String>,
+    /// Only install a downloaded script if its SHA-256 digest matches
+    #[arg(long, value_name = "HEX")]
+    pub sha256: Option<String>,
+    /// What to do with programs that are already installed.
+    /// `--force` is the same as `--on-conflict force`.
+    #[arg(long, value_enum, default_value_t = OnConflict::Fail)]
+    pub on_conflict: OnConflict,
+    /// Keep the local modifications of programs that also changed upstream
+    #[arg(long, conflicts_with = "theirs", default_value_t = false)]
+    pub ours: bool,
+    /// Take the upstream version of programs that were modified locally
+    #[arg(long, default_value_t = false)]
+    pub theirs: bool,
+    /// Create the bin entries once installed, as `spm relink` does
+    #[arg(long, default_value_t = false)]
+    pub link: bool,
     /// Specify a base url if you would like to install a program hosted in
     /// a differet git repository other than GitHub.
     /// Use `-u` for short.
@@ -82,13 +218,148 @@ pub struct InstallArguments {
 #[derive(Debug, Parser)]
 pub struct ListArguments;
 
+#[derive(Debug, Args)]
+pub struct SearchArguments {
+    /// Keywords to match against program names and origins
+    #[arg(required = true)]
+    pub keywords: Vec<String>,
+    /// Show at most this many results
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+    /// Search the programs listed by the registries instead of the installed ones
+    #[arg(long, default_value_t = false)]
+    pub remote: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MetaArguments {
+    #[command(subcommand)]
+    pub action: MetaAction,
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryArguments {
+    #[command(subcommand)]
+    pub action: RegistryAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RegistryAction {
+    /// Add a registry, or change the url of an existing one
+    Add {
+        /// The name to refer to the registry by
+        name: String,
+        /// Where its JSON index is: an HTTP(S) URL, a `file://` URL or a path
+        url: String,
+    },
+    /// Remove a registry
+    Remove {
+        /// The name of the registry
+        name: String,
+    },
+    /// List the registries in the order they are searched
+    List,
+}
+
+#[derive(Debug, Args)]
+pub struct CleanArguments {
+    /// Remove the temporary files that spm processes left behind when older than this
+    #[arg(long, value_name = "HOURS", default_value_t = 24)]
+    pub older_than: u64,
+    /// Also remove the cached clones of git repositories
+    #[arg(long, default_value_t = false)]
+    pub cache: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MetaAction {
+    /// Set a metadata field, e.g. `spm meta set backup default_args "--verbose --target s3"`
+    Set {
+        /// Name of the installed program
+        name: String,
+        /// The field to set
+        key: MetadataKey,
+        /// The new value of the field
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Clear a metadata field
+    Unset {
+        /// Name of the installed program
+        name: String,
+        /// The field to clear
+        key: MetadataKey,
+    },
+}
+
+#[derive(Debug, Args)]
+#[command(group = clap::ArgGroup::new("actions").required(true).multiple(false))]
+pub struct ScheduleArguments {
+    /// Name of the installed program to schedule
+    #[arg(group = "actions", requires = "cron")]
+    pub name: Option<String>,
+    /// When to run the program, as a cron expression, e.g. "0 3 * * *" or @daily
+    pub cron: Option<String>,
+    /// Arguments to pass to the program
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
+    /// Remove the schedule of a program
+    #[arg(long, group = "actions", value_name = "NAME")]
+    pub remove: Option<String>,
+    /// Show the programs scheduled by spm
+    #[arg(long, group = "actions", default_value_t = false)]
+    pub list: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExplainArguments {
+    /// The error code to explain, e.g. `E0002`
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArguments {
+    /// Name of the installed program
+    pub name: String,
+    /// Print the details as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+#[command(group = clap::ArgGroup::new("sources").required(true).multiple(false))]
+pub struct UpdateArguments {
+    /// Name of the installed program to update
+    #[arg(group = "sources")]
+    pub name: Option<String>,
+    /// Update every installed program
+    #[arg(long, group = "sources", default_value_t = false)]
+    pub all: bool,
+    /// Keep the local modifications of programs that also changed upstream
+    #[arg(long, conflicts_with = "theirs", default_value_t = false)]
+    pub ours: bool,
+    /// Take the upstream version of programs that were modified locally
+    #[arg(long, default_value_t = false)]
+    pub theirs: bool,
+}
+
 #[derive(Debug, Args)]
 #[command(group = clap::ArgGroup::new("sources").required(true).multiple(false))]
 pub struct UninstallArguments {
-    /// Index to your shell script in the bookmark.
-    /// Can be obtained with `spm list`
+    /// Indexes of the programs shown by `spm list`, their names, or keywords of them
     #[arg(group = "sources")]
-    pub expression: String,
+    pub expressions: Vec<String>,
+    /// Uninstall every installed program
+    #[arg(long, group = "sources", default_value_t = false)]
+    pub all: bool,
+    /// Uninstall every program installed from a git repository.
+    /// Accepts the repository url, its origin shown in `spm list`,
+    /// or a trailing part of it, like `owner/repository`, that a single source ends with.
+    #[arg(long, group = "sources")]
+    pub from: Option<String>,
+    /// Do not ask for confirmation
+    #[arg(short, long, default_value_t = false)]
+    pub yes: bool,
 }
 
 #[derive(Debug, Args)]
@@ -105,6 +376,66 @@ pub struct NewArguments {
     /// Name the generated shell script
     #[arg(group = "sources")]
     pub name: String,
+    /// The interpreter of the script: sh, bash, zsh or cmd
+    #[arg(short, long, default_value = "sh", value_parser = str::parse::<ShellType>)]
+    pub interpreter: ShellType,
+    /// A one line description, written as a comment at the top of the script
+    #[arg(short, long)]
+    pub description: Option<String>,
+    /// Start from a built-in template (default, minimal) or from a script,
+    /// whose {{name}}, {{interpreter}} and {{description}} are filled in
+    #[arg(short, long)]
+    pub template: Option<String>,
+    /// Use the given values without asking for the others in a terminal
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct RelinkArguments;
+
+/// The values of `--on-conflict`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnConflict {
+    /// Ask for each conflict, with the option to answer for all of them
+    Ask,
+    /// Keep the installed program
+    Skip,
+    /// Overwrite the installed program
+    Force,
+    /// Refuse to overwrite the installed program
+    Fail,
+}
+
+impl From<OnConflict> for ConflictPolicy {
+    fn from(value: OnConflict) -> Self {
+        match value {
+            OnConflict::Ask => ConflictPolicy::Ask,
+            OnConflict::Skip => ConflictPolicy::Skip,
+            OnConflict::Force => ConflictPolicy::Force,
+            OnConflict::Fail => ConflictPolicy::Fail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionsMode {
+    /// Write the completion script to the conventional location of the shell
+    Install,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArguments {
+    /// Use `install` to write the completions to where your shell loads them.
+    /// Prints them to stdout when omitted.
+    #[arg(value_enum)]
+    pub mode: Option<CompletionsMode>,
+    /// The shell to generate completions for. Detected from `$SHELL` by default.
+    #[arg(short, long, value_enum)]
+    pub shell: Option<Shell>,
+    /// Print the completions to stdout even in `install` mode
+    #[arg(long, default_value_t = false)]
+    pub stdout: bool,
 }
 
 #[derive(Debug, Args)]
@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Error, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{properties::DEFAULT_CONFIGURATION_FILE, utilities::get_spm_root_directory};
+
+/// User configuration, stored as `config.json` in the spm root directory
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Configuration {
+    /// Mirrors tried in order when fetching from a base url fails,
+    /// keyed by that base url
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirrors: BTreeMap<String, Vec<String>>,
+    /// How much each kind of keyword match counts when searching programs
+    #[serde(default, skip_serializing_if = "SearchWeights::is_default")]
+    pub search: SearchWeights,
+    /// Registries listing programs that can be installed by name, tried in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registries: Vec<Registry>,
+}
+
+/// An index of programs, see `spm registry add`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Registry {
+    pub name: String,
+    /// Where the JSON index is: an HTTP(S) URL, a `file://` URL or a path
+    pub url: String,
+}
+
+/// Scores added for each keyword matching a program
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct SearchWeights {
+    /// The keyword is the name of the program
+    pub exact: usize,
+    /// The keyword is part of the name of the program
+    pub name: usize,
+    /// The keyword is part of where the program was installed from
+    pub origin: usize,
+}
+
+impl Default for SearchWeights {
+    fn default() -> Self {
+        Self {
+            exact: 2,
+            name: 1,
+            origin: 1,
+        }
+    }
+}
+
+impl SearchWeights {
+    /// Whether the weights are the default ones, which are not written to the file
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Configuration {
+    /// Returns the path to the configuration file
+    pub fn get_path() -> Result<PathBuf, Error> {
+        Ok(get_spm_root_directory()?.join(DEFAULT_CONFIGURATION_FILE))
+    }
+
+    /// Load the configuration, or the default one if there is no file yet
+    pub fn load() -> Result<Self, Error> {
+        let path: PathBuf = Self::get_path()?;
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let content: String = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Write the configuration, creating the spm root directory if needed
+    pub fn save(&self) -> Result<(), Error> {
+        let path: PathBuf = Self::get_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Returns the url itself followed by the same repository path on each
+    /// configured mirror of its base url.
+    pub fn get_candidate_urls(&self, url: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = vec![url.to_string()];
+
+        for (base_url, mirrors) in &self.mirrors {
+            let base_url: &str = base_url.trim_end_matches('/');
+
+            if let Some(repository_path) = url.strip_prefix(base_url) {
+                // Only match on whole path segments
+                if !repository_path.is_empty() && !repository_path.starts_with('/') {
+                    continue;
+                }
+
+                for mirror in mirrors {
+                    candidates.push(format!("{}{}", mirror.trim_end_matches('/'), repository_path));
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_search_weights_are_not_written() {
+        let configuration = Configuration::default();
+        assert_eq!(serde_json::to_string(&configuration).unwrap(), "{}");
+
+        let configuration = Configuration {
+            search: SearchWeights {
+                exact: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let json: String = serde_json::to_string(&configuration).unwrap();
+        assert!(json.contains(r#""exact":5"#));
+        assert_eq!(serde_json::from_str::<Configuration>(&json).unwrap(), configuration);
+    }
+
+    #[test]
+    fn missing_search_weights_take_their_default() {
+        let configuration: Configuration = serde_json::from_str(r#"{"search": {"name": 4}}"#).unwrap();
+        assert_eq!(
+            configuration.search,
+            SearchWeights {
+                name: 4,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Configuration>("{}").unwrap().search,
+            SearchWeights::default()
+        );
+    }
+}
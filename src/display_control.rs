@@ -1,7 +1,10 @@
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
-use anyhow::{Error, Result};
-use console::style;
+use anyhow::{Error, Result, anyhow};
+use clap::ValueEnum;
+use console::{Key, Term, style};
 use prettytable::{Cell, Row, Table};
 
 #[derive(Debug, Clone, Copy)]
@@ -10,31 +13,256 @@ pub enum Level {
     Error,
     Warn,
     Input,
+    /// Details of what spm is doing, only shown with `--verbose`
+    Debug,
 }
 
+/// How much spm tells about what it is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors, warnings and the output that was asked for
+    Quiet,
+    Normal,
+    /// Also every path, URL and command spm works with
+    Verbose,
+}
+
+/// The verbosity of this invocation
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Change the verbosity, e.g. for `--quiet` or `--verbose`
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn get_verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// When to color the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Only in a terminal, and unless `NO_COLOR` is set
+    Auto,
+    /// Even when the output is piped
+    Always,
+    Never,
+}
+
+/// Apply a color mode to stdout and stderr, each of which may or may not be a terminal
+pub fn set_color_mode(mode: ColorMode) {
+    let is_enabled: Option<bool> = match mode {
+        // The terminal detection of console is kept, `NO_COLOR` is checked
+        // here as well because it only honors it on some platforms
+        ColorMode::Auto => std::env::var_os("NO_COLOR")
+            .filter(|value| !value.is_empty())
+            .map(|_| false),
+        ColorMode::Always => Some(true),
+        ColorMode::Never => Some(false),
+    };
+
+    if let Some(is_enabled) = is_enabled {
+        console::set_colors_enabled(is_enabled);
+        console::set_colors_enabled_stderr(is_enabled);
+    }
+}
+
+/// Whether interactive widgets may be used when a terminal is available
+static IS_FANCY_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Whether the last line written is a progress line waiting to be finished
+static IS_PROGRESS_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Longest text shown in a single table cell before it is truncated
+const MAX_DISPLAY_CELL_LENGTH: usize = 200;
+
+/// Neutralize text that may come from outside of spm, such as program names,
+/// paths or error messages, so that it cannot spoof output or alter the terminal.
+/// Escape sequences are removed, and other control and bidirectional formatting
+/// characters are shown escaped.
+pub fn sanitize_for_display(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            match chars.next() {
+                // Control sequence, ends with a byte in the range @ to ~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // Operating system command, ends with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let is_bidi_control: bool =
+            matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}');
+        if c.is_control() || is_bidi_control {
+            sanitized.extend(c.escape_default());
+        } else {
+            sanitized.push(c);
+        }
+    }
+
+    sanitized
+}
+
+/// Shorten text to at most `max_length` characters, marking the cut
+pub fn truncate_for_display(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_length.saturating_sub(3)).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// Render a path so that it can be copied into a shell, quoting it when needed
+pub fn quote_path(path: &Path) -> String {
+    shell_words::quote(&path.display().to_string()).to_string()
+}
+
+/// Display a message. Errors and warnings go to stderr, so that they are
+/// neither piped along with the output nor hidden by `--quiet`.
 pub fn display_message(level: Level, message: &str) {
     let indentation: String = ">> ".to_string();
+    let message: String = sanitize_for_display(message);
 
     match level {
-        Level::Logging => println!("{}{}", indentation, style(message).green()),
-        Level::Error => println!("{}{}", indentation, style(message).red().bold()),
-        Level::Warn => println!("{}{}", indentation, style(message).red()),
+        Level::Logging if get_verbosity() > Verbosity::Quiet => {
+            println!("{}{}", indentation, style(message).green())
+        }
+        Level::Logging => {}
+        Level::Error => eprintln!("{}{}", indentation, style(message).for_stderr().red().bold()),
+        Level::Warn => eprintln!("{}{}", indentation, style(message).for_stderr().red()),
         Level::Input => print!("{}{} ", indentation, style(message).blue()),
+        Level::Debug if get_verbosity() == Verbosity::Verbose => {
+            eprintln!("{}{}", indentation, style(message).for_stderr().dim())
+        }
+        Level::Debug => {}
     }
 }
 
+/// Display a status line under a message, hidden with `--quiet`
 pub fn display_tree_message(indent_level: usize, message: &str) {
+    if get_verbosity() == Verbosity::Quiet {
+        return;
+    }
+    display_tree_output(indent_level, message);
+}
+
+/// Display a line of the output that was asked for, which `--quiet` keeps
+pub fn display_tree_output(indent_level: usize, message: &str) {
     let indentation: String = "\t".repeat(indent_level);
+    let message: String = sanitize_for_display(message);
     println!("{}>> {}", indentation, style(message).green());
 }
 
+/// Display the details of an error under its message, on stderr
+pub fn display_error_tree_message(indent_level: usize, message: &str) {
+    let indentation: String = "\t".repeat(indent_level);
+    let message: String = sanitize_for_display(message);
+    eprintln!("{}>> {}", indentation, style(message).for_stderr().red());
+}
+
+/// Display a side note that is less important than a regular message
+pub fn display_note(message: &str) {
+    if get_verbosity() == Verbosity::Quiet {
+        return;
+    }
+    println!(">> {}", style(sanitize_for_display(message)).dim());
+}
+
+/// Show the progress of a long operation, rewriting the same terminal line
+/// each time. Nothing is shown when stdout is not a terminal, or with `--quiet`.
+pub fn display_progress(message: &str) {
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() || get_verbosity() == Verbosity::Quiet {
+        return;
+    }
+
+    let _ = write!(
+        stdout,
+        "\r\x1b[2K>> {}",
+        style(sanitize_for_display(message)).dim()
+    )
+    .and_then(|_| stdout.flush());
+    IS_PROGRESS_PENDING.store(true, Ordering::Relaxed);
+}
+
+/// End the progress line, so that the next message starts on a line of its own
+pub fn finish_progress() {
+    if IS_PROGRESS_PENDING.swap(false, Ordering::Relaxed) {
+        println!();
+    }
+}
+
+/// Format a number of bytes with a binary unit, e.g. `1.5 MiB`
+pub fn format_byte_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size: f64 = bytes as f64;
+    let mut unit: usize = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Display labelled counts aligned in a column, dimming the zero ones
+pub fn display_counts(rows: &[(&str, usize)]) {
+    if get_verbosity() == Verbosity::Quiet {
+        return;
+    }
+    let width: usize = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    for (label, count) in rows {
+        let line: String = format!("{:<width$}  {}", label, count, width = width);
+        if *count == 0 {
+            println!(">> {}", style(line).dim());
+        } else {
+            println!(">> {}", style(line).yellow());
+        }
+    }
+}
+
 pub fn display_form(column_labels: Vec<&str>, rows: &Vec<Vec<String>>) {
     let mut table = Table::new();
     let top_line: Vec<Cell> = column_labels.iter().map(|item| Cell::new(item)).collect();
     table.add_row(Row::new(top_line));
 
     for row in rows {
-        table.add_row(Row::new(row.iter().map(|item| Cell::new(item)).collect()));
+        table.add_row(Row::new(
+            row.iter()
+                .map(|item| {
+                    let text: String = sanitize_for_display(item);
+                    Cell::new(&truncate_for_display(&text, MAX_DISPLAY_CELL_LENGTH))
+                })
+                .collect(),
+        ));
     }
 
     table.printstd();
@@ -51,3 +279,221 @@ pub fn input_message(prompt: &str) -> Result<String, Error> {
 
     Ok(input)
 }
+
+/// Ask a yes or no question, where anything but yes means no
+pub fn confirm(prompt: &str) -> Result<bool, Error> {
+    let answer: String = input_message(&format!("{} [y/N]:", prompt))?
+        .trim()
+        .to_lowercase();
+
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// Allow or forbid the interactive widgets, e.g. for `--no-fancy`
+pub fn set_fancy_enabled(is_enabled: bool) {
+    IS_FANCY_ENABLED.store(is_enabled, Ordering::Relaxed);
+}
+
+/// Whether the interactive widgets can be used in this terminal
+fn is_fancy_available() -> bool {
+    IS_FANCY_ENABLED.load(Ordering::Relaxed)
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+/// What a key press did to a selection list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStep {
+    Moved,
+    Selected(usize),
+    Cancelled,
+}
+
+/// The cursor of a selection list, kept apart from the rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionState {
+    cursor: usize,
+    count: usize,
+}
+
+impl SelectionState {
+    pub fn new(count: usize) -> Self {
+        Self { cursor: 0, count }
+    }
+
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Apply a key press, wrapping around at both ends of the list.
+    /// An empty list has nothing to select, so confirming cancels it.
+    pub fn apply(&mut self, key: &Key) -> SelectionStep {
+        if self.count == 0 {
+            return match key {
+                Key::Enter | Key::Char(' ') | Key::Escape | Key::Char('q') => SelectionStep::Cancelled,
+                _ => SelectionStep::Moved,
+            };
+        }
+
+        match key {
+            Key::ArrowUp | Key::Char('k') => {
+                self.cursor = (self.cursor + self.count - 1) % self.count;
+            }
+            Key::ArrowDown | Key::Tab | Key::Char('j') => {
+                self.cursor = (self.cursor + 1) % self.count;
+            }
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.count - 1,
+            Key::Enter | Key::Char(' ') => return SelectionStep::Selected(self.cursor),
+            Key::Escape | Key::Char('q') => return SelectionStep::Cancelled,
+            _ => {}
+        }
+
+        SelectionStep::Moved
+    }
+}
+
+/// Let the user move through the items with the arrow keys and pick one
+fn pick_with_arrow_keys(prompt: &str, items: &[String]) -> Result<usize, Error> {
+    if items.is_empty() {
+        return Err(anyhow!("There is nothing to select"));
+    }
+
+    let terminal: Term = Term::stdout();
+    let mut state = SelectionState::new(items.len());
+
+    display_message(Level::Input, prompt);
+    println!("{}", style("(arrow keys to move, enter to select, esc to cancel)").dim());
+    terminal.hide_cursor()?;
+
+    let result: Result<usize, Error> = loop {
+        for (index, item) in items.iter().enumerate() {
+            let item: String = sanitize_for_display(item);
+            if index == state.get_cursor() {
+                terminal.write_line(&format!("{} {}", style(">").cyan(), style(item).cyan().bold()))?;
+            } else {
+                terminal.write_line(&format!("  {}", item))?;
+            }
+        }
+
+        let step: SelectionStep = match terminal.read_key() {
+            Ok(key) => state.apply(&key),
+            Err(error) => break Err(error.into()),
+        };
+        terminal.clear_last_lines(items.len())?;
+
+        match step {
+            SelectionStep::Moved => {}
+            SelectionStep::Selected(index) => break Ok(index),
+            SelectionStep::Cancelled => break Err(anyhow!("No option was selected")),
+        }
+    };
+
+    terminal.show_cursor()?;
+    result
+}
+
+/// Ask the user to pick one item of a list. Returns the index of the item.
+/// Uses an arrow-key list in a terminal, and a numbered prompt otherwise.
+pub fn pick_from_list(prompt: &str, items: &[String]) -> Result<usize, Error> {
+    if items.is_empty() {
+        return Err(anyhow!("There is nothing to select"));
+    }
+
+    if is_fancy_available() {
+        return pick_with_arrow_keys(prompt, items);
+    }
+
+    for (index, item) in items.iter().enumerate() {
+        display_tree_output(1, &format!("{}: {}", index + 1, item));
+    }
+    let selection: usize = input_message(prompt)?
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| anyhow!("Invalid selection"))?;
+
+    if selection < 1 || selection > items.len() {
+        return Err(anyhow!("Invalid selection"));
+    }
+
+    Ok(selection - 1)
+}
+
+/// Ask the user to pick one of the options, by key or by label.
+/// Returns the index of the selected option.
+pub fn select_option(prompt: &str, options: &[(&str, &str)]) -> Result<usize, Error> {
+    if is_fancy_available() {
+        let items: Vec<String> = options.iter().map(|(_, label)| label.to_string()).collect();
+        return pick_with_arrow_keys(prompt, &items);
+    }
+
+    let choices: Vec<String> = options
+        .iter()
+        .map(|(key, label)| format!("{} = {}", key, label))
+        .collect();
+    let answer: String = input_message(&format!("{} ({}):", prompt, choices.join(", ")))?
+        .trim()
+        .to_lowercase();
+
+    if answer.is_empty() {
+        return Err(anyhow!("No option was selected"));
+    }
+
+    options
+        .iter()
+        .position(|(key, label)| answer == *key || answer == *label)
+        .ok_or_else(|| anyhow!("Invalid selection: {}", answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_wraps_around_both_ends() {
+        let mut state = SelectionState::new(3);
+        assert_eq!(state.apply(&Key::ArrowUp), SelectionStep::Moved);
+        assert_eq!(state.get_cursor(), 2);
+        assert_eq!(state.apply(&Key::ArrowDown), SelectionStep::Moved);
+        assert_eq!(state.get_cursor(), 0);
+        state.apply(&Key::Char('j'));
+        state.apply(&Key::Tab);
+        assert_eq!(state.get_cursor(), 2);
+        state.apply(&Key::Char('k'));
+        assert_eq!(state.get_cursor(), 1);
+    }
+
+    #[test]
+    fn selection_jumps_selects_and_cancels() {
+        let mut state = SelectionState::new(4);
+        state.apply(&Key::End);
+        assert_eq!(state.get_cursor(), 3);
+        assert_eq!(state.apply(&Key::Enter), SelectionStep::Selected(3));
+        state.apply(&Key::Home);
+        assert_eq!(state.apply(&Key::Char(' ')), SelectionStep::Selected(0));
+        assert_eq!(state.apply(&Key::Escape), SelectionStep::Cancelled);
+        assert_eq!(state.apply(&Key::Char('q')), SelectionStep::Cancelled);
+        assert_eq!(state.apply(&Key::Char('x')), SelectionStep::Moved);
+        assert_eq!(state.get_cursor(), 0);
+    }
+
+    #[test]
+    fn paths_are_quoted_for_a_shell_when_needed() {
+        assert_eq!(quote_path(Path::new("/usr/bin/tool")), "/usr/bin/tool");
+        assert_eq!(quote_path(Path::new("/tmp/with space.sh")), "'/tmp/with space.sh'");
+        assert_eq!(quote_path(Path::new("/tmp/it's $HOME")), "'/tmp/it'\\''s $HOME'");
+    }
+
+    #[test]
+    fn empty_selection_cannot_be_moved_or_selected() {
+        let mut state = SelectionState::new(0);
+        for key in [Key::ArrowUp, Key::ArrowDown, Key::Home, Key::End] {
+            assert_eq!(state.apply(&key), SelectionStep::Moved);
+            assert_eq!(state.get_cursor(), 0);
+        }
+        assert_eq!(state.apply(&Key::Enter), SelectionStep::Cancelled);
+
+        assert!(pick_from_list("Pick one", &[]).is_err());
+    }
+}
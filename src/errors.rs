@@ -0,0 +1,308 @@
+use std::fmt::Display;
+
+use anyhow::Error;
+
+use crate::display_control::{Level, display_error_tree_message, display_message};
+
+/// Errors gathered while working through many items, so that a batch
+/// operation can carry on past a failure and report all of them at the end
+#[derive(Debug, Default)]
+pub struct ErrorCollection {
+    errors: Vec<(String, Error)>,
+}
+
+impl ErrorCollection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the error of the item described by `context`
+    pub fn push(&mut self, context: impl Into<String>, error: Error) {
+        self.errors.push((context.into(), error));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Display every error grouped by item, under a summary line
+    pub fn display(&self, summary: &str) {
+        display_message(Level::Error, &format!("{} ({})", summary, self.len()));
+        for (indent_level, line) in self.get_tree_lines() {
+            display_error_tree_message(indent_level, &line);
+        }
+    }
+
+    /// The lines of the error tree with their indentation: each item, then
+    /// the causes of its error one level below
+    fn get_tree_lines(&self) -> Vec<(usize, String)> {
+        let mut lines: Vec<(usize, String)> = Vec::new();
+        for (context, error) in &self.errors {
+            lines.push((1, context.clone()));
+            lines.extend(error.chain().map(|cause| (2, cause.to_string())));
+        }
+
+        lines
+    }
+}
+
+impl Display for ErrorCollection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} error(s)", self.len())
+    }
+}
+
+impl std::error::Error for ErrorCollection {}
+
+/// Documented failures, which `spm explain <code>` describes in detail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    ProgramNotFound,
+    ProgramAlreadyInstalled,
+    UnsupportedScript,
+    UnknownVersion,
+    ChecksumMismatch,
+    ScriptFailed,
+    InterpreterNotFound,
+    ProgramsLocked,
+}
+
+impl ErrorCode {
+    /// Every documented error code, in order
+    pub const ALL: [ErrorCode; 8] = [
+        ErrorCode::ProgramNotFound,
+        ErrorCode::ProgramAlreadyInstalled,
+        ErrorCode::UnsupportedScript,
+        ErrorCode::UnknownVersion,
+        ErrorCode::ChecksumMismatch,
+        ErrorCode::ScriptFailed,
+        ErrorCode::InterpreterNotFound,
+        ErrorCode::ProgramsLocked,
+    ];
+
+    /// The position of the code in `ALL`. The match has no wildcard, so a new
+    /// variant does not compile until it is given the next position and listed.
+    const fn get_position(self) -> usize {
+        match self {
+            ErrorCode::ProgramNotFound => 0,
+            ErrorCode::ProgramAlreadyInstalled => 1,
+            ErrorCode::UnsupportedScript => 2,
+            ErrorCode::UnknownVersion => 3,
+            ErrorCode::ChecksumMismatch => 4,
+            ErrorCode::ScriptFailed => 5,
+            ErrorCode::InterpreterNotFound => 6,
+            ErrorCode::ProgramsLocked => 7,
+        }
+    }
+
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            ErrorCode::ProgramNotFound => "E0001",
+            ErrorCode::ProgramAlreadyInstalled => "E0002",
+            ErrorCode::UnsupportedScript => "E0003",
+            ErrorCode::UnknownVersion => "E0004",
+            ErrorCode::ChecksumMismatch => "E0005",
+            ErrorCode::ScriptFailed => "E0006",
+            ErrorCode::InterpreterNotFound => "E0007",
+            ErrorCode::ProgramsLocked => "E0008",
+        }
+    }
+
+    pub fn get_summary(&self) -> &'static str {
+        match self {
+            ErrorCode::ProgramNotFound => "No installed program matches the given name",
+            ErrorCode::ProgramAlreadyInstalled => "The program is already installed",
+            ErrorCode::UnsupportedScript => "The file is not a shell script spm can install",
+            ErrorCode::UnknownVersion => "The git repository has no such tag, branch or commit",
+            ErrorCode::ChecksumMismatch => "The downloaded script does not match the expected SHA-256",
+            ErrorCode::ScriptFailed => "The script exited with a non-zero status",
+            ErrorCode::InterpreterNotFound => "The interpreter of the script is not on the PATH",
+            ErrorCode::ProgramsLocked => "Another spm process is changing the installed programs",
+        }
+    }
+
+    /// What causes the failure, how to fix it and which commands are related
+    pub fn get_explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::ProgramNotFound => {
+                "spm looked for an installed program with this name, or for programs whose names \
+contain the keywords, and found none.
+
+Common fixes:
+  - Check the spelling with `spm list`, or look for it with `spm search <keywords>`.
+  - Install the program first with `spm install <path or url>`.
+  - To run a script that is not installed, pass its path, e.g. `spm run ./script.sh`."
+            }
+            ErrorCode::ProgramAlreadyInstalled => {
+                "A program with the same name is already installed, and the conflict policy is to \
+fail rather than overwrite it.
+
+Common fixes:
+  - Reinstall with `--force` (-F) to overwrite the installed copy.
+  - Choose another policy with `--on-conflict ask|skip|force|fail`.
+  - Use `spm update <name>` to refresh a program from where it was installed.
+  - Install a URL under another name with `--as <name>`."
+            }
+            ErrorCode::UnsupportedScript => {
+                "spm only installs shell scripts: files with the `.sh` extension, or downloads that \
+start with a shebang or look like shell code.
+
+Common fixes:
+  - Rename the file so that it ends with `.sh`.
+  - Check that the path points at a file rather than a directory.
+  - For URLs, make sure the link serves the raw script rather than an HTML page."
+            }
+            ErrorCode::UnknownVersion => {
+                "The tag, branch or commit given with `--version`, or after `@` in the URL, does not \
+exist in the cloned repository. The error lists the tags that do exist.
+
+Common fixes:
+  - Pick one of the listed tags.
+  - Check that the branch was pushed to the remote.
+  - Leave the version out to install the default branch."
+            }
+            ErrorCode::ChecksumMismatch => {
+                "The script downloaded with `--sha256` has a different SHA-256 digest than expected. \
+The script may have changed upstream, or it may have been tampered with. Nothing was installed.
+
+Common fixes:
+  - Make sure the expected digest belongs to this exact URL.
+  - Review the new content of the script before trusting a new digest."
+            }
+            ErrorCode::ScriptFailed => {
+                "The script ran, but exited with a non-zero status. spm exits with the same status, \
+or with 128 + signal when the script was killed by a signal.
+
+Common fixes:
+  - Read the output of the script above the error.
+  - Check the script for syntax errors with `spm check <name or path>`."
+            }
+            ErrorCode::InterpreterNotFound => {
+                "The shebang of the script names an interpreter, such as bash or zsh, that cannot be \
+found on the PATH, so spm refused to install or run it. `cmd` scripts only run on Windows.
+
+Common fixes:
+  - Install the interpreter with your package manager, or add its directory to the PATH.
+  - Pass `--ignore-interpreter` to install the script anyway and run it with sh instead.
+  - Change the shebang if the script does not need that interpreter."
+            }
+            ErrorCode::ProgramsLocked => {
+                "Commands that change the installed programs, such as install, update and uninstall, \
+hold a lock on `~/.spm/spm.lock` while they run, so that two of them never write the same \
+files. Another spm process holds it now. The lock is released when that process exits.
+
+Common fixes:
+  - Pass `--wait` to wait for the other process instead of failing.
+  - In CI, run the spm steps that share a home one after the other."
+            }
+        }
+    }
+
+    /// Create an error with this code
+    pub fn error(self, message: impl Into<String>) -> Error {
+        SpmError {
+            code: self,
+            message: message.into(),
+        }
+        .into()
+    }
+}
+
+// Every code sits at its own position in `ALL`, so none is listed twice or left out
+const _: () = {
+    let mut index: usize = 0;
+    while index < ErrorCode::ALL.len() {
+        assert!(ErrorCode::ALL[index].get_position() == index);
+        index += 1;
+    }
+};
+
+impl std::str::FromStr for ErrorCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|code| code.get_code().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!("Unknown error code: {}", s))
+    }
+}
+
+/// An error with a documented code, which points to `spm explain`
+#[derive(Debug)]
+pub struct SpmError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl Display for SpmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (run `spm explain {}` for more)",
+            self.message,
+            self.code.get_code()
+        )
+    }
+}
+
+impl std::error::Error for SpmError {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::*;
+
+    #[test]
+    fn error_codes_are_listed_in_order() {
+        for (index, code) in ErrorCode::ALL.into_iter().enumerate() {
+            assert_eq!(code.get_position(), index);
+            assert_eq!(code.get_code(), format!("E{:04}", index + 1));
+            assert_eq!(code.get_code().parse::<ErrorCode>().unwrap(), code);
+            assert!(!code.get_summary().is_empty());
+            assert!(!code.get_explanation().is_empty());
+        }
+        assert!("E9999".parse::<ErrorCode>().is_err());
+    }
+
+    #[test]
+    fn collections_count_their_errors() {
+        let mut collection = ErrorCollection::new();
+        assert!(collection.is_empty());
+
+        collection.push("one", anyhow!("first"));
+        collection.push("two", anyhow!("second"));
+        assert!(!collection.is_empty());
+        assert_eq!(collection.len(), 2);
+        assert_eq!(collection.to_string(), "2 error(s)");
+    }
+
+    #[test]
+    fn tree_lines_list_each_item_with_its_causes() {
+        let mut collection = ErrorCollection::new();
+        collection.push("tool", anyhow!("not found"));
+        collection.push(
+            "other",
+            Err::<(), Error>(anyhow!("permission denied"))
+                .context("Failed to remove program file")
+                .unwrap_err(),
+        );
+
+        assert_eq!(
+            collection.get_tree_lines(),
+            vec![
+                (1, "tool".to_string()),
+                (2, "not found".to_string()),
+                (1, "other".to_string()),
+                (2, "Failed to remove program file".to_string()),
+                (2, "permission denied".to_string()),
+            ]
+        );
+        assert!(ErrorCollection::new().get_tree_lines().is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::utilities::remove_temp_directory;
+
+/// What pressing Ctrl-C does at the current step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum InterruptMode {
+    /// Exit with 130, like shells report an interrupted command
+    Exit,
+    /// Remove the temporary folder of this process, then exit with 130
+    RemoveTemporaryFiles,
+    /// Leave the interrupt to the child process in the foreground
+    Ignore,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(InterruptMode::Exit as u8);
+
+/// Handle Ctrl-C for the whole process, as the current step asks
+pub fn set_interrupt_handler() {
+    let _ = ctrlc::set_handler(|| {
+        let mode: u8 = MODE.load(Ordering::SeqCst);
+        if mode == InterruptMode::Ignore as u8 {
+            return;
+        }
+
+        if mode == InterruptMode::RemoveTemporaryFiles as u8 {
+            let _ = remove_temp_directory();
+        }
+        std::process::exit(130);
+    });
+}
+
+/// Run a clone or download, so that an interrupted one leaves nothing in the temporary folder
+pub fn while_fetching<T>(operation: impl FnOnce() -> T) -> T {
+    with_mode(InterruptMode::RemoveTemporaryFiles, operation)
+}
+
+/// Wait for a script or shell in the foreground. It receives Ctrl-C itself
+/// and decides whether to stop, so spm reports its status rather than exiting.
+pub fn while_child_runs<T>(operation: impl FnOnce() -> T) -> T {
+    with_mode(InterruptMode::Ignore, operation)
+}
+
+fn with_mode<T>(mode: InterruptMode, operation: impl FnOnce() -> T) -> T {
+    let previous: u8 = MODE.swap(mode as u8, Ordering::SeqCst);
+    let result: T = operation();
+    MODE.store(previous, Ordering::SeqCst);
+    result
+}
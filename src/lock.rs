@@ -0,0 +1,63 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Error, Result};
+
+use crate::display_control::{Level, display_message};
+use crate::errors::ErrorCode;
+use crate::properties::DEFAULT_LOCK_FILE;
+use crate::utilities::get_spm_root_directory;
+
+/// Keeps other spm processes from changing the installed programs.
+/// The lock is released when it is dropped, or when the process exits.
+#[derive(Debug)]
+pub struct ProgramsLock {
+    _file: File,
+}
+
+impl ProgramsLock {
+    /// Take the lock, failing when another process holds it unless `is_waiting`
+    pub fn acquire(is_waiting: bool) -> Result<Self, Error> {
+        // Commands like `clean` may run before anything else created the root
+        let root_directory: PathBuf = get_spm_root_directory()?;
+        std::fs::create_dir_all(&root_directory)?;
+        let path: PathBuf = root_directory.join(DEFAULT_LOCK_FILE);
+        let mut file: File = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(_) => {}
+            Err(TryLockError::WouldBlock) if is_waiting => {
+                display_message(Level::Logging, "Waiting for another spm process to finish...");
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => {
+                // The holder wrote its pid, which some platforms do not let us read
+                let mut pid: String = String::new();
+                let _ = file.read_to_string(&mut pid);
+                let holder: String = match pid.trim() {
+                    "" => String::new(),
+                    pid => format!(" (pid {})", pid),
+                };
+                return Err(ErrorCode::ProgramsLocked.error(format!(
+                    "Another spm process is running{}. Wait for it to finish, or pass --wait",
+                    holder
+                )));
+            }
+            Err(TryLockError::Error(error)) => return Err(error.into()),
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
@@ -1,76 +1,218 @@
 mod arguments;
+mod configuration;
 mod display_control;
+mod errors;
+mod interrupt;
+mod lock;
 mod program;
+mod progress;
 mod properties;
+mod registry;
+mod schedule;
 mod shell;
+mod source;
 mod utilities;
 
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
-use arguments::{Arguments, Commands};
+use arguments::{Arguments, Commands, CompletionsMode, MetaAction, RegistryAction};
 use clap::{Parser, crate_version};
+use clap_complete::Shell;
 
-use display_control::display_message;
-use program::{Program, ProgramManager};
+use display_control::{display_message, display_tree_message, display_tree_output, quote_path};
+use errors::{ErrorCode, ErrorCollection};
+use program::{
+    ConflictPolicy, ConflictResolver, InstallOutcome, LocalChangePolicy, Program, ProgramManager,
+};
+use shell::ScriptExitError;
+use source::SourceOptions;
 use utilities::{
-    execute_run_command, show_programs,
+    ask_new_program_details, execute_run_command, open_program_shell, show_install_summary, show_program_info,
+    show_programs, show_search_results, uninstall_programs, uninstall_programs_from_source,
 };
 
 fn main() {
     // Parse command line arguments
     let arguments: Arguments = Arguments::parse();
-    // Initialize a program manager
-    let program_manager: ProgramManager = match ProgramManager::new() {
+    display_control::set_color_mode(if arguments.no_color {
+        display_control::ColorMode::Never
+    } else {
+        arguments.color
+    });
+    display_control::set_verbosity(match (arguments.quiet, arguments.verbose) {
+        (true, _) => display_control::Verbosity::Quiet,
+        (_, true) => display_control::Verbosity::Verbose,
+        _ => display_control::Verbosity::Normal,
+    });
+    // Listen for progress first, so that every failure below still reports the end of the run
+    if let Some(path) = &arguments.progress_file {
+        match progress::JsonLinesReporter::create(path) {
+            Ok(reporter) => progress::set_progress_reporter(Box::new(reporter)),
+            Err(error) => {
+                exit_early(&format!("Cannot write progress to {}: {}", path.display(), error));
+            }
+        }
+    }
+    if let Some(home) = &arguments.home
+        && let Err(error) = utilities::set_spm_root_directory(home)
+    {
+        exit_early(&format!("Cannot use {} as the home of spm: {}", home.display(), error));
+    }
+    display_control::set_fancy_enabled(!arguments.no_fancy);
+
+    interrupt::set_interrupt_handler();
+    shell::set_interpreter_check_enabled(!arguments.ignore_interpreter);
+    utilities::set_offline(arguments.offline);
+    // Initialize a program manager, setting the directories up only for the
+    // commands that need them
+    let program_manager = if arguments.commands.uses_programs() {
+        ProgramManager::new()
+    } else {
+        ProgramManager::open()
+    };
+    let program_manager: ProgramManager = match program_manager {
         Ok(result) => result,
-        Err(error) => {
-            display_message(
-                display_control::Level::Error,
-                &format!("{}", error.to_string()),
-            );
-            return;
+        Err(error) => exit_early(&error.to_string()),
+    };
+
+    // Keep other processes away from the programs while changing them
+    let _programs_lock: Option<lock::ProgramsLock> = if arguments.commands.changes_programs() {
+        match lock::ProgramsLock::acquire(arguments.wait) {
+            Ok(lock) => Some(lock),
+            Err(error) => exit_early(&error.to_string()),
         }
+    } else {
+        None
     };
 
     // Check if the binary directory is in the user's PATH
-    let _ = utilities::check_bin_directory_in_path();
+    if arguments.commands.uses_bin_directory() {
+        let _ = utilities::check_bin_directory_in_path();
+    }
+
+    // Whether the command failed, which is reported through the exit code
+    let mut is_failed: bool = false;
+    // The exit code to use on failure, when a script decided it
+    let mut exit_code: i32 = 1;
 
     // Map the arguments to corresponding code logics
     match arguments.commands {
         Commands::Run(subcommand) => {
-            match execute_run_command(&program_manager, subcommand.expression, &subcommand.args) {
+            let result = if subcommand.shell {
+                open_program_shell(&program_manager, &subcommand.expression)
+            } else {
+                execute_run_command(
+                    &program_manager,
+                    subcommand.expression,
+                    &subcommand.args,
+                    !subcommand.no_default_args,
+                )
+            };
+
+            match result {
                 Ok(_) => {}
-                Err(error) => display_message(
-                    display_control::Level::Error,
-                    &format!("{}", error.to_string()),
-                ),
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("{}", error.to_string()),
+                    );
+                    // Exit with the same code as the script
+                    if let Some(script_error) = error.downcast_ref::<ScriptExitError>() {
+                        exit_code = script_error.code;
+                    }
+                    is_failed = true;
+                }
             }
         }
-        Commands::Install(subcommand) => {
-            // Check if the path is a Git URL
-            if subcommand.path.starts_with("http://") || subcommand.path.starts_with("https://") || subcommand.path.starts_with("git@") {
-                match program_manager.install_from_git(&subcommand.path, subcommand.force) {
-                    Ok(_) => display_message(
+        Commands::Install(mut subcommand) => {
+            // A bare program name is installed from the registries that list it
+            match registry::resolve_registry_source(&subcommand.path) {
+                Ok(Some((registry_name, source))) => {
+                    display_message(
                         display_control::Level::Logging,
-                        "Programs from Git repository installed successfully!",
-                    ),
-                    Err(error) => display_message(
-                        display_control::Level::Error,
-                        &format!("Error installing programs from Git repository: {}", error.to_string()),
-                    ),
+                        &format!("Installing {} from registry {}: {}", subcommand.path, registry_name, source),
+                    );
+                    subcommand.path = source;
                 }
-            } else {
-                let program_path = Path::new(&subcommand.path).to_path_buf();
+                Ok(None) => {}
+                Err(error) => {
+                    display_message(display_control::Level::Error, &error.to_string());
+                    is_failed = true;
+                }
+            }
 
-                // Install the program
-                match program_manager.install_program(&program_path, subcommand.force) {
-                    Ok(_) => display_message(
-                        display_control::Level::Logging,
-                        "Program installation succeeded.",
-                    ),
-                    Err(error) => display_message(
-                        display_control::Level::Error,
-                        &format!("{}", error.to_string()),
-                    ),
+            if !is_failed {
+                // Programs already installed are handled as the flags ask
+                let mut resolver = ConflictResolver::new(if subcommand.force {
+                    ConflictPolicy::Force
+                } else {
+                    subcommand.on_conflict.into()
+                })
+                .with_local_changes(LocalChangePolicy::from_flags(subcommand.ours, subcommand.theirs));
+
+                let options = SourceOptions {
+                    git_reference: subcommand.git_reference.as_deref(),
+                    program_name: subcommand.as_name.as_deref(),
+                    expected_sha256: subcommand.sha256.as_deref(),
+                    is_single_file: subcommand.single_file,
+                    is_full_clone: subcommand.full_clone,
+                    subdirectory: subcommand.subdir.as_deref(),
+                    base_url: Some(&subcommand.base_url),
+                };
+
+                match program_manager.install_from_source(&subcommand.path, &options, &mut resolver) {
+                    Ok(summary) => {
+                        show_install_summary(&summary);
+                        if summary.failures.is_empty() {
+                            display_message(
+                                display_control::Level::Logging,
+                                "Program installation succeeded.",
+                            );
+                        } else {
+                            summary.failures.display("Some programs failed to install");
+                            is_failed = true;
+                        }
+
+                        // Put the installed programs on PATH right away
+                        if subcommand.link && !is_failed {
+                            let names: Vec<String> = [
+                                &summary.added,
+                                &summary.modified,
+                                &summary.unchanged,
+                                &summary.kept,
+                                &summary.removed,
+                            ]
+                            .into_iter()
+                            .flatten()
+                            .cloned()
+                            .collect();
+
+                            match program_manager.link_programs(&names) {
+                                Ok(report) => display_message(
+                                    display_control::Level::Logging,
+                                    &format!(
+                                        "Bin entries: {} created, {} updated, {} removed, {} unchanged.",
+                                        report.created, report.updated, report.removed, report.unchanged
+                                    ),
+                                ),
+                                Err(error) => {
+                                    display_message(
+                                        display_control::Level::Error,
+                                        &format!("Error creating the bin entries: {}", error),
+                                    );
+                                    is_failed = true;
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        display_message(
+                            display_control::Level::Error,
+                            &format!("Error installing programs: {}", error),
+                        );
+                        is_failed = true;
+                    }
                 }
             }
         }
@@ -84,43 +226,394 @@ fn main() {
                         display_control::Level::Error,
                         &format!("Error retrieving installed programs: {}", error.to_string()),
                     );
+                    is_failed = true;
                 }
             };
         }
+        Commands::Search(subcommand) if subcommand.remote => {
+            match registry::search_registries(&subcommand.keywords) {
+                Ok(mut results) => {
+                    if let Some(limit) = subcommand.limit {
+                        results.truncate(limit);
+                    }
+
+                    if results.is_empty() {
+                        display_message(
+                            display_control::Level::Logging,
+                            &format!("No registry lists a program matching: {}", subcommand.keywords.join(" ")),
+                        );
+                    } else {
+                        utilities::show_registry_results(&results);
+                    }
+                }
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error searching registries: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+        }
+        Commands::Search(subcommand) => {
+            match program_manager.scored_keyword_search(&subcommand.keywords.join(","), true) {
+                Ok(mut results) => {
+                    if let Some(limit) = subcommand.limit {
+                        results.truncate(limit);
+                    }
+
+                    if results.is_empty() {
+                        display_message(
+                            display_control::Level::Logging,
+                            &format!("No programs matched: {}", subcommand.keywords.join(" ")),
+                        );
+                    } else {
+                        show_search_results(&results);
+                    }
+                }
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error searching programs: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+        }
         Commands::Uninstall(subcommand) => {
-            match program_manager.uninstall_program_by_name(subcommand.expression) {
+            let result = match &subcommand.from {
+                Some(source) => uninstall_programs_from_source(&program_manager, source, subcommand.yes),
+                None => uninstall_programs(
+                    &program_manager,
+                    &subcommand.expressions,
+                    subcommand.all,
+                    subcommand.yes,
+                ),
+            };
+            match result {
+                Ok((_, failures)) if !failures.is_empty() => {
+                    failures.display("Some programs failed to uninstall");
+                    is_failed = true;
+                }
+                Ok((0, _)) => display_message(
+                    display_control::Level::Logging,
+                    "Nothing was uninstalled.",
+                ),
                 Ok(_) => display_message(
                     display_control::Level::Logging,
-                    "Program uninstalled successfully.",
+                    "Programs uninstalled successfully.",
                 ),
-                Err(error) => display_message(
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error uninstalling programs: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+        }
+        Commands::Info(subcommand) => match program_manager.get_program_info(subcommand.name) {
+            Ok(info) if subcommand.json => match serde_json::to_string_pretty(&info) {
+                Ok(json) => println!("{}", json),
+                Err(error) => {
+                    display_message(display_control::Level::Error, &format!("{}", error));
+                    is_failed = true;
+                }
+            },
+            Ok(info) => show_program_info(&info),
+            Err(error) => {
+                display_message(
                     display_control::Level::Error,
-                    &format!("Error uninstalling program: {}", error.to_string()),
-                ),
+                    &format!("Error retrieving program: {}", error),
+                );
+                is_failed = true;
+            }
+        },
+        Commands::Meta(subcommand) => {
+            let (name, key, value) = match subcommand.action {
+                MetaAction::Set { name, key, value } => (name, key, Some(value)),
+                MetaAction::Unset { name, key } => (name, key, None),
+            };
+
+            match program_manager.set_program_metadata(name, key, value) {
+                Ok(_) => display_message(display_control::Level::Logging, "Program metadata updated."),
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error updating program metadata: {}", error),
+                    );
+                    is_failed = true;
+                }
             }
         }
-        Commands::Check(_) => {
-            display_message(
-                display_control::Level::Logging,
-                "The 'Check' feature is still under development.",
-            );
+        Commands::Update(subcommand) => {
+            let programs: Result<Vec<Program>, _> = match subcommand.name {
+                Some(name) => program_manager.get_program_by_name(name).map(|program| vec![program]),
+                None => program_manager.get_installed_programs(),
+            };
+
+            match programs {
+                Ok(programs) => {
+                    let mut failures = ErrorCollection::new();
+                    for (name, outcome) in program_manager.update_programs(
+                        &programs,
+                        LocalChangePolicy::from_flags(subcommand.ours, subcommand.theirs),
+                    ) {
+                        match outcome {
+                            Ok(InstallOutcome::Unchanged) => {
+                                display_tree_message(1, &format!("{}: up to date", name))
+                            }
+                            Ok(InstallOutcome::KeptLocal) => display_tree_message(
+                                1,
+                                &format!("{}: kept local modifications", name),
+                            ),
+                            Ok(_) => display_tree_message(1, &format!("{}: updated", name)),
+                            Err(error) => failures.push(name, error),
+                        }
+                    }
+
+                    if !failures.is_empty() {
+                        failures.display("Some programs failed to update");
+                        is_failed = true;
+                    }
+                }
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error updating programs: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
         }
-        Commands::New(subcommand) => {
-            let program_file_path: PathBuf =
-                Path::new("./").join(format!("{}.sh", &subcommand.name));
-            let program = Program::new(subcommand.name, crate::shell::ShellType::Sh);
+        Commands::Check(subcommand) => {
+            // Check a script file, or an installed program by name
+            let path: &Path = Path::new(&subcommand.expression);
+            let program: Result<Program, _> = if path.is_file() {
+                Program::from_file(path)
+            } else {
+                program_manager.get_program_by_name(subcommand.expression.clone())
+            };
+
+            match program {
+                Ok(program) => {
+                    let script_path: &Path = program.get_program_path().unwrap_or(path);
 
-            match program_manager.create_program(&program_file_path, &program) {
+                    match shell::check_shell_script_syntax(script_path, program.get_interpreter()) {
+                        Ok(None) => display_tree_output(
+                            1,
+                            &format!("{} ({}): passed", quote_path(script_path), program.get_interpreter()),
+                        ),
+                        Ok(Some(details)) => {
+                            display_message(
+                                display_control::Level::Error,
+                                &format!("{} ({}): failed", quote_path(script_path), program.get_interpreter()),
+                            );
+                            for line in details.lines() {
+                                display_tree_output(1, line);
+                            }
+                            is_failed = true;
+                        }
+                        Err(error) => {
+                            display_message(
+                                display_control::Level::Error,
+                                &format!("{}: {}", quote_path(script_path), error),
+                            );
+                            is_failed = true;
+                        }
+                    }
+                }
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error checking program: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+        }
+        Commands::New(mut subcommand) => {
+            // In a terminal, confirm the details unless told to take them as given
+            let answers: Result<(), anyhow::Error> = if !subcommand.yes && std::io::stdin().is_terminal() {
+                ask_new_program_details(&mut subcommand)
+            } else {
+                Ok(())
+            };
+
+            match answers.and_then(|_| {
+                let program_file_path: PathBuf =
+                    Path::new("./").join(format!("{}.sh", &subcommand.name));
+                let program = Program::new(subcommand.name.clone(), subcommand.interpreter);
+
+                program_manager.create_program(
+                    &program_file_path,
+                    &program,
+                    subcommand.description.as_deref(),
+                    subcommand.template.as_deref(),
+                )
+            }) {
                 Ok(_) => display_message(
                     display_control::Level::Logging,
                     "Program created successfully.",
                 ),
-                Err(error) => display_message(
-                    display_control::Level::Error,
-                    &format!("{}", error.to_string()),
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("{}", error.to_string()),
+                    );
+                    is_failed = true;
+                }
+            };
+        }
+        Commands::Relink(_) => {
+            match program_manager.relink_programs() {
+                Ok(report) => display_message(
+                    display_control::Level::Logging,
+                    &format!(
+                        "Relink finished: {} created, {} updated, {} removed, {} unchanged.",
+                        report.created, report.updated, report.removed, report.unchanged
+                    ),
                 ),
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error relinking programs: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+        }
+        Commands::Completions(subcommand) => {
+            let shell: Option<Shell> = subcommand.shell.or_else(utilities::detect_user_shell);
+
+            match shell {
+                None => {
+                    display_message(
+                        display_control::Level::Error,
+                        "Could not detect your shell. Please specify it with `--shell`.",
+                    );
+                    is_failed = true;
+                }
+                Some(shell)
+                    if subcommand.stdout || subcommand.mode != Some(CompletionsMode::Install) =>
+                {
+                    let _ = std::io::stdout().write_all(&utilities::generate_completions(shell));
+                }
+                Some(shell) => match utilities::install_completions(shell) {
+                    Ok((path, hint)) => {
+                        display_message(
+                            display_control::Level::Logging,
+                            &format!("Completions for {} written to {}", shell, quote_path(&path)),
+                        );
+                        if let Some(hint) = hint {
+                            display_tree_message(1, &hint);
+                        }
+                    }
+                    Err(error) => {
+                        display_message(
+                            display_control::Level::Error,
+                            &format!("Error installing completions: {}", error),
+                        );
+                        is_failed = true;
+                    }
+                },
+            }
+        }
+        Commands::Schedule(subcommand) => {
+            let result: Result<(), anyhow::Error> = if subcommand.list {
+                utilities::show_schedule()
+            } else if let Some(name) = subcommand.remove {
+                utilities::unschedule_program(&name)
+            } else {
+                utilities::schedule_program(
+                    &program_manager,
+                    subcommand.name.unwrap_or_default(),
+                    &subcommand.cron.unwrap_or_default(),
+                    &subcommand.args,
+                )
+            };
+
+            if let Err(error) = result {
+                display_message(
+                    display_control::Level::Error,
+                    &format!("Error scheduling program: {}", error),
+                );
+                is_failed = true;
+            }
+        }
+        Commands::Registry(subcommand) => {
+            let result: Result<(), anyhow::Error> = match subcommand.action {
+                RegistryAction::Add { name, url } => {
+                    registry::add_registry(&name, &url).map(|is_replaced| {
+                        display_message(
+                            display_control::Level::Logging,
+                            if is_replaced { "Registry updated." } else { "Registry added." },
+                        )
+                    })
+                }
+                RegistryAction::Remove { name } => registry::remove_registry(&name)
+                    .map(|_| display_message(display_control::Level::Logging, "Registry removed.")),
+                RegistryAction::List => utilities::show_registries(),
             };
+
+            if let Err(error) = result {
+                display_message(
+                    display_control::Level::Error,
+                    &format!("Error managing registries: {}", error),
+                );
+                is_failed = true;
+            }
         }
+        Commands::Clean(subcommand) => {
+            let max_age = std::time::Duration::from_secs(subcommand.older_than * 60 * 60);
+            match utilities::sweep_temp_directories(max_age) {
+                Ok(removed) => display_message(
+                    display_control::Level::Logging,
+                    &format!("Removed {} temporary entries.", removed),
+                ),
+                Err(error) => {
+                    display_message(
+                        display_control::Level::Error,
+                        &format!("Error removing temporary files: {}", error),
+                    );
+                    is_failed = true;
+                }
+            }
+
+            if subcommand.cache {
+                match utilities::clean_cache() {
+                    Ok(true) => display_message(display_control::Level::Logging, "Cache removed."),
+                    Ok(false) => display_message(display_control::Level::Logging, "The cache is empty."),
+                    Err(error) => {
+                        display_message(
+                            display_control::Level::Error,
+                            &format!("Error removing the cache: {}", error),
+                        );
+                        is_failed = true;
+                    }
+                }
+            }
+        }
+        Commands::Explain(subcommand) => match subcommand.code {
+            Some(code) => match code.parse::<ErrorCode>() {
+                Ok(code) => {
+                    display_message(
+                        display_control::Level::Logging,
+                        &format!("{}: {}", code.get_code(), code.get_summary()),
+                    );
+                    println!("\n{}", code.get_explanation());
+                }
+                Err(error) => {
+                    display_message(display_control::Level::Error, &format!("{}", error));
+                    is_failed = true;
+                }
+            },
+            None => {
+                for code in ErrorCode::ALL {
+                    display_tree_output(0, &format!("{}: {}", code.get_code(), code.get_summary()));
+                }
+            }
+        },
         Commands::Version(_) => {
             display_message(
                 display_control::Level::Logging,
@@ -129,5 +622,21 @@ fn main() {
         }
     }
 
-    return;
+    let _ = utilities::remove_temp_directory();
+
+    progress::report_progress(progress::ProgressEvent::new(
+        "done",
+        if is_failed { "failed" } else { "succeeded" },
+    ));
+
+    if is_failed {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Stop before running the command, telling progress listeners that the run failed
+fn exit_early(message: &str) -> ! {
+    display_message(display_control::Level::Error, message);
+    progress::report_progress(progress::ProgressEvent::new("done", "failed"));
+    std::process::exit(1);
 }
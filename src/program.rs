@@ -1,14 +1,28 @@
 use std::io::Write;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fs::DirEntry,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use anyhow::{Error, Result, anyhow};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use crate::properties::{DEFAULT_SPM_FOLDER, DEFAULT_SPM_PROGRAMS_FOLDER};
-use crate::shell::ShellType;
+use crate::configuration::{Configuration, SearchWeights};
+use crate::display_control::{Level, display_message, sanitize_for_display, select_option};
+use crate::properties::{
+    DEFAULT_SPM_BIN_FOLDER, DEFAULT_SPM_FOLDER, DEFAULT_SPM_PROGRAMS_FOLDER,
+    PROGRAM_DIRECTORY_ENVIRONMENT_VARIABLE, PROGRAM_NAME_ENVIRONMENT_VARIABLE,
+    PROGRAM_PATH_ENVIRONMENT_VARIABLE, SPM_HOME_ENVIRONMENT_VARIABLE,
+};
+use crate::errors::{ErrorCode, ErrorCollection};
+use crate::interrupt::while_fetching;
+use crate::progress::{ProgressEvent, report_progress};
+use crate::source::{ResolvedSource, SourceOptions, select_source_resolver};
+use crate::utilities::compute_sha256;
+use crate::shell::{ShellType, ensure_interpreter_available};
 
 /// Represent a shell script program
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, Ord, Eq)]
@@ -19,6 +33,14 @@ pub struct Program {
     path_to_program: Option<PathBuf>,
     // The interpreter used for this program
     interpreter: ShellType,
+    // The source the program was installed from, if any
+    origin: Option<String>,
+    // What kind of source the program was installed from, if recorded
+    source_kind: Option<SourceKind>,
+    // The git tag, branch or commit the program was installed at, if any
+    version: Option<String>,
+    // When the program was installed, in seconds since the Unix epoch, if recorded
+    installed_at: Option<u64>,
 }
 
 impl Program {
@@ -26,7 +48,11 @@ impl Program {
         Self { 
             name, 
             path_to_program: None,
-            interpreter 
+            interpreter,
+            origin: None,
+            source_kind: None,
+            version: None,
+            installed_at: None,
         }
     }
 
@@ -49,6 +75,10 @@ impl Program {
             name: file_name,
             path_to_program: Some(file_path.to_path_buf()),
             interpreter,
+            origin: None,
+            source_kind: None,
+            version: None,
+            installed_at: None,
         })
     }
 
@@ -56,13 +86,365 @@ impl Program {
         &self.name
     }
 
-    pub fn get_program_path(&self) -> Option<&str> {
-        self.path_to_program.as_ref().map(|p| p.as_os_str().to_str().unwrap())
+    pub fn get_program_path(&self) -> Option<&Path> {
+        self.path_to_program.as_deref()
     }
 
     pub fn get_interpreter(&self) -> &ShellType {
         &self.interpreter
     }
+
+    pub fn get_origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    /// Where the program came from, as shown in listings: `local` for the
+    /// programs installed from a file on this machine
+    pub fn get_origin_label(&self) -> &str {
+        match (self.source_kind, self.get_origin()) {
+            (Some(SourceKind::Local), _) | (_, None) => "local",
+            (_, Some(origin)) => origin,
+        }
+    }
+
+    pub fn get_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn get_installed_at(&self) -> Option<u64> {
+        self.installed_at
+    }
+
+    /// Environment variables exported to the program when spm runs it, so that
+    /// it can locate itself regardless of the working directory
+    pub fn get_environment(&self) -> Result<Vec<(String, String)>, Error> {
+        let path: PathBuf = self
+            .get_program_path()
+            .ok_or_else(|| anyhow!("Program path not available"))?
+            .canonicalize()?;
+        let directory: &Path = path.parent().unwrap_or(&path);
+
+        Ok(vec![
+            (PROGRAM_NAME_ENVIRONMENT_VARIABLE.to_string(), self.name.clone()),
+            (PROGRAM_PATH_ENVIRONMENT_VARIABLE.to_string(), path.display().to_string()),
+            (PROGRAM_DIRECTORY_ENVIRONMENT_VARIABLE.to_string(), directory.display().to_string()),
+            (
+                SPM_HOME_ENVIRONMENT_VARIABLE.to_string(),
+                crate::utilities::get_spm_root_directory()?.display().to_string(),
+            ),
+        ])
+    }
+}
+
+/// The kinds of sources programs are installed from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// A script on this machine
+    Local,
+    /// A git repository
+    Git,
+    /// A script downloaded from a URL
+    Url,
+}
+
+/// Sidecar metadata stored next to an installed program
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ProgramMetadata {
+    /// Identifier of the repository the program was installed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// What kind of source `source` is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SourceKind>,
+    /// The mirror that served the program when the source itself failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
+    /// The git tag, branch or commit the program was installed at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Arguments passed to the program before the ones given to `spm run`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_args: Option<String>,
+    /// The commit the version resolved to when the program was installed from git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// When the program was installed, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<u64>,
+    /// Whether the install overwrote an existing program with `--force`
+    #[serde(default, rename = "forced", skip_serializing_if = "std::ops::Not::not")]
+    pub is_forced: bool,
+    /// SHA-256 digest of the program as installed, to detect local modifications
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+/// The metadata files already reported as broken, so that each is reported once
+static BROKEN_METADATA: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+impl ProgramMetadata {
+    /// Load the metadata of a program, falling back to the default one with a
+    /// warning when the file is broken, so that the program stays usable
+    pub fn load_or_default(path_to_program: &Path) -> Self {
+        Self::load(path_to_program).unwrap_or_else(|error| {
+            let is_new: bool = BROKEN_METADATA
+                .lock()
+                .map_or(true, |mut reported| reported.insert(path_to_program.to_path_buf()));
+            if is_new {
+                let program_name = path_to_program.file_stem().unwrap_or_default().to_string_lossy();
+                display_message(
+                    Level::Warn,
+                    &format!("Ignoring the metadata of {}: {}", program_name, error),
+                );
+            }
+            Self::default()
+        })
+    }
+
+    /// Load the metadata of a program, or the default one if it has none
+    pub fn load(path_to_program: &Path) -> Result<Self, Error> {
+        let metadata_path: PathBuf = get_metadata_path(path_to_program);
+
+        if !metadata_path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let content: String = std::fs::read_to_string(&metadata_path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", metadata_path.display(), e))
+    }
+
+    /// Save the metadata next to the program, removing the file when empty
+    pub fn save(&self, path_to_program: &Path) -> Result<(), Error> {
+        let metadata_path: PathBuf = get_metadata_path(path_to_program);
+
+        if *self == Self::default() {
+            if metadata_path.exists() {
+                std::fs::remove_file(&metadata_path)?;
+            }
+            return Ok(());
+        }
+
+        std::fs::write(&metadata_path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// Metadata fields of an installed program that can be edited by the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MetadataKey {
+    /// Arguments always passed to the program by `spm run`
+    #[value(name = "default_args")]
+    DefaultArgs,
+}
+
+/// Everything spm knows about an installed program
+#[derive(Debug, Serialize)]
+pub struct ProgramInfo {
+    pub name: String,
+    pub interpreter: ShellType,
+    pub path: PathBuf,
+    /// The bin entry that exposes the program, if it exists
+    pub bin_entry: Option<PathBuf>,
+    #[serde(flatten)]
+    pub metadata: ProgramMetadata,
+}
+
+/// Summary of the changes made to the bin directory by a relink
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RelinkReport {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Outcome of updating a single program, keyed by its name
+pub type UpdateResult = (String, Result<InstallOutcome, Error>);
+
+/// What happened to a program passed to an install
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallOutcome {
+    /// The program was not installed before
+    Installed,
+    /// An installed program with the same name was overwritten
+    Replaced,
+    /// An installed program with the same name and content was kept
+    Unchanged,
+    /// The program was already installed and left untouched
+    Skipped,
+    /// The installed program was edited locally and the edits were kept
+    KeptLocal,
+}
+
+impl InstallOutcome {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            InstallOutcome::Installed => "installed",
+            InstallOutcome::Replaced => "updated",
+            InstallOutcome::Unchanged => "unchanged",
+            InstallOutcome::Skipped => "skipped",
+            InstallOutcome::KeptLocal => "kept local modifications",
+        }
+    }
+}
+
+/// The progress event telling listeners how installing a program ended
+fn get_install_progress_event(
+    phase: &'static str,
+    program_name: &str,
+    outcome: &Result<InstallOutcome, Error>,
+) -> ProgressEvent {
+    let message: String = match outcome {
+        Ok(outcome) => outcome.get_label().to_string(),
+        Err(error) => format!("failed: {}", error),
+    };
+
+    ProgressEvent::new(phase, message).with_program(program_name)
+}
+
+/// What a batch install changed, by program name
+#[derive(Debug, Default)]
+pub struct InstallSummary {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub kept: Vec<String>,
+    pub removed: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failures: ErrorCollection,
+}
+
+impl InstallSummary {
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.modified.is_empty() || !self.removed.is_empty()
+    }
+}
+
+/// How to handle programs that are already installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Ask for each conflict, with the option to answer for all of them
+    Ask,
+    /// Keep the installed program
+    Skip,
+    /// Overwrite the installed program
+    Force,
+    /// Refuse to overwrite the installed program
+    Fail,
+}
+
+/// What to do with an installed program that was edited locally and changed upstream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalChangePolicy {
+    /// Ask for each modified program
+    #[default]
+    Ask,
+    /// Keep the local modifications
+    Ours,
+    /// Replace the local modifications with the upstream version
+    Theirs,
+}
+
+impl LocalChangePolicy {
+    /// The policy selected by the `--ours` and `--theirs` flags
+    pub fn from_flags(ours: bool, theirs: bool) -> Self {
+        match (ours, theirs) {
+            (true, _) => LocalChangePolicy::Ours,
+            (_, true) => LocalChangePolicy::Theirs,
+            _ => LocalChangePolicy::Ask,
+        }
+    }
+}
+
+/// Applies a conflict policy over one invocation, remembering "all" answers
+#[derive(Debug, Clone)]
+pub struct ConflictResolver {
+    policy: ConflictPolicy,
+    local_changes: LocalChangePolicy,
+}
+
+impl ConflictResolver {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            local_changes: LocalChangePolicy::default(),
+        }
+    }
+
+    /// Resolve the programs modified both locally and upstream with this policy
+    pub fn with_local_changes(mut self, local_changes: LocalChangePolicy) -> Self {
+        self.local_changes = local_changes;
+        self
+    }
+
+    pub fn get_policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    /// Decide whether a program modified both locally and upstream should take
+    /// the upstream version. Returns `false` to keep the local modifications.
+    pub fn should_take_upstream(
+        &mut self,
+        program_name: &str,
+        installed: &Path,
+        upstream: &Path,
+    ) -> Result<bool, Error> {
+        loop {
+            match self.local_changes {
+                LocalChangePolicy::Ours => return Ok(false),
+                LocalChangePolicy::Theirs => return Ok(true),
+                LocalChangePolicy::Ask => {
+                    let selection: usize = select_option(
+                        &format!(
+                            "`{}` was modified locally and changed upstream. Which version do you want?",
+                            program_name
+                        ),
+                        &[("k", "keep local"), ("t", "take upstream"), ("d", "show diff")],
+                    )?;
+
+                    match selection {
+                        0 => return Ok(false),
+                        1 => return Ok(true),
+                        _ => display_diff(installed, upstream)?,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decide whether an installed program should be overwritten.
+    /// Returns `false` when it should be skipped.
+    pub fn should_overwrite(&mut self, program_name: &str) -> Result<bool, Error> {
+        match self.policy {
+            ConflictPolicy::Force => Ok(true),
+            ConflictPolicy::Skip => Ok(false),
+            ConflictPolicy::Fail => Err(ErrorCode::ProgramAlreadyInstalled.error(
+                "The program already exists. Use `--force` (-F) flag to force an install or update",
+            )),
+            ConflictPolicy::Ask => {
+                let selection: usize = select_option(
+                    &format!("`{}` is already installed. Overwrite it?", program_name),
+                    &[("y", "yes"), ("n", "no"), ("a", "yes to all"), ("s", "skip all")],
+                )?;
+
+                match selection {
+                    0 => Ok(true),
+                    1 => Ok(false),
+                    2 => {
+                        self.policy = ConflictPolicy::Force;
+                        Ok(true)
+                    }
+                    _ => {
+                        self.policy = ConflictPolicy::Skip;
+                        Ok(false)
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -72,13 +454,12 @@ pub struct ProgramManager {
 
 impl ProgramManager {
     pub fn new() -> Result<Self, Error> {
-        let root_directory: PathBuf = dirs::home_dir()
-            .ok_or_else(|| anyhow!("Failed to locate home directory"))?
-            .join(DEFAULT_SPM_FOLDER);
+        let root_directory: PathBuf = crate::utilities::get_spm_root_directory()?;
+        let programs_directory: PathBuf = root_directory.join(DEFAULT_SPM_PROGRAMS_FOLDER);
 
-        if !root_directory.exists() {
+        if !programs_directory.exists() {
             // Create the programs folder
-            match std::fs::create_dir_all(&root_directory.join("programs")) {
+            match std::fs::create_dir_all(&programs_directory) {
                 Ok(_) => (),
                 Err(e) => {
                     return Err(anyhow!(
@@ -93,9 +474,16 @@ impl ProgramManager {
         Ok(Self { root_directory })
     }
 
+    /// Use the spm root directory as it is, without creating anything in it
+    pub fn open() -> Result<Self, Error> {
+        Ok(Self {
+            root_directory: crate::utilities::get_spm_root_directory()?,
+        })
+    }
+
     /// Returns the path to the binary directory where executable scripts are symlinked.
     pub fn get_bin_directory(&self) -> Result<PathBuf, Error> {
-        let bin_dir = self.root_directory.join("bin");
+        let bin_dir = self.root_directory.join(DEFAULT_SPM_BIN_FOLDER);
 
         // Create the bin directory if it doesn't exist
         if !bin_dir.exists() {
@@ -105,6 +493,71 @@ impl ProgramManager {
         Ok(bin_dir)
     }
 
+    /// Regenerates the bin entry of every installed program and removes the
+    /// entries whose program no longer exists. Running it twice changes nothing.
+    pub fn relink_programs(&self) -> Result<RelinkReport, Error> {
+        let bin_directory: PathBuf = self.get_bin_directory()?;
+        let programs_directory: PathBuf = self.access_program_installation_directory();
+        let mut report = RelinkReport::default();
+        let mut expected_entries: Vec<PathBuf> = Vec::new();
+
+        for program in self.get_installed_programs()? {
+            let target: &Path = program
+                .path_to_program
+                .as_deref()
+                .ok_or_else(|| anyhow!("Program path not available"))?;
+            let entry: PathBuf = get_bin_entry_path(&bin_directory, program.get_name());
+            link_bin_entry(&entry, target, &programs_directory, &mut report)?;
+            expected_entries.push(entry);
+        }
+
+        // Remove the entries left behind by programs that no longer exist,
+        // leaving alone the ones that point somewhere else
+        for entry in std::fs::read_dir(&bin_directory)? {
+            let path: PathBuf = entry?.path();
+
+            // Skip entries that are being written by a concurrent relink
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+
+            if !expected_entries.contains(&path) && is_managed_bin_entry(&path, &programs_directory) {
+                std::fs::remove_file(&path)?;
+                report.removed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Creates or refreshes the bin entries of the given programs only, and
+    /// removes the entries of those that are no longer installed.
+    pub fn link_programs(&self, program_names: &[String]) -> Result<RelinkReport, Error> {
+        let bin_directory: PathBuf = self.get_bin_directory()?;
+        let programs_directory: PathBuf = self.access_program_installation_directory();
+        let installed_programs: Vec<Program> = self.get_installed_programs()?;
+        let mut report = RelinkReport::default();
+
+        for name in program_names {
+            let entry: PathBuf = get_bin_entry_path(&bin_directory, name);
+            let program: Option<&Program> = installed_programs.iter().find(|program| program.get_name() == name);
+
+            match program.and_then(|program| program.path_to_program.as_deref()) {
+                Some(target) => link_bin_entry(&entry, target, &programs_directory, &mut report)?,
+                None if is_managed_bin_entry(&entry, &programs_directory) => {
+                    std::fs::remove_file(&entry)?;
+                    report.removed += 1;
+                }
+                None => {}
+            }
+        }
+
+        Ok(report)
+    }
+
     /// Retrieves a `Program` object by its name.
     pub fn get_program_by_name(&self, program_name: String) -> Result<Program, Error> {
         let installed_programs: Vec<Program> = self.get_installed_programs()?;
@@ -116,59 +569,95 @@ impl ProgramManager {
             }
         }
 
-        Err(anyhow!("Program with name '{}' not found", program_name))
+        Err(ErrorCode::ProgramNotFound.error(format!("Program with name '{}' not found", program_name)))
     }
 
     pub fn keyword_search(&self, keywords: &str) -> Result<Vec<Program>, Error> {
-        let words: Vec<String> = keywords
-            .split(",")
-            .map(|keyword: &str| keyword.to_lowercase())
-            .collect();
-        let mut matched_programs: Vec<(Program, usize)> = Vec::new();
-
-        if let Ok(programs) = self.get_installed_programs() {
-            for program in programs {
-                let program_name: String = program.get_name().to_lowercase();
-
-                // If exactly matches the program name
-                if program_name == keywords.to_lowercase() {
-                    matched_programs.push((program.clone(), 2)); // Higher score for exact match
-                    continue;
-                }
+        Ok(self
+            .scored_keyword_search(keywords, false)?
+            .into_iter()
+            .map(|(program, _)| program)
+            .collect())
+    }
 
-                let mut match_score = 0;
+    /// Search installed programs by comma separated keywords, returning each
+    /// match with its score. Optionally also match against program origins.
+    pub fn scored_keyword_search(
+        &self,
+        keywords: &str,
+        is_origin_included: bool,
+    ) -> Result<Vec<(Program, usize)>, Error> {
+        let words: Vec<(String, String)> = parse_search_keywords(keywords);
+
+        // A configuration that cannot be read does not keep programs from being found
+        let weights: SearchWeights = match Configuration::load() {
+            Ok(configuration) => configuration.search,
+            Err(error) => {
+                display_message(Level::Warn, &format!("{}. Using the default search weights", error));
+                SearchWeights::default()
+            }
+        };
 
-                for word in words.iter() {
-                    // Skip if the keyword is empty
-                    if word.is_empty() {
-                        continue;
-                    }
+        // Skip the programs that did not match, and order the others by score,
+        // then by name for equal scores
+        let mut matched_programs: Vec<(Program, usize)> = self
+            .get_installed_programs()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|program| {
+                let score: usize = get_match_score(&program, &words, &weights, is_origin_included);
+                (program, score)
+            })
+            .filter(|(_, score)| *score > 0)
+            .collect();
+        matched_programs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.get_name().cmp(b.0.get_name())));
 
-                    // When a keyword is found in the name
-                    if program_name.contains(word) {
-                        match_score += 1;
-                    }
-                }
+        Ok(matched_programs)
+    }
 
-                // Add the program with its match score if any matches found
-                if match_score > 0 {
-                    matched_programs.push((program.clone(), match_score));
+    /// Set or clear a metadata field of an installed program
+    pub fn set_program_metadata(
+        &self,
+        program_name: String,
+        key: MetadataKey,
+        value: Option<String>,
+    ) -> Result<(), Error> {
+        let program: Program = self.get_program_by_name(program_name)?;
+        let path: &Path = program
+            .get_program_path()
+            .ok_or_else(|| anyhow!("The program has no installed path"))?;
+        let mut metadata: ProgramMetadata = ProgramMetadata::load_or_default(path);
+
+        match key {
+            MetadataKey::DefaultArgs => {
+                // Make sure the arguments can be split before saving them
+                if let Some(value) = &value {
+                    shell_words::split(value)
+                        .map_err(|e| anyhow!("Invalid arguments '{}': {}", value, e))?;
                 }
+                metadata.default_args = value;
             }
         }
 
-        // Sort the programs by match count in descending order
-        matched_programs.sort_by(|a, b| b.1.cmp(&a.1));
-
-        let mut results: Vec<Program> = Vec::new();
-        for matched_program in matched_programs {
-            // Skip the programs if the score is zero
-            if matched_program.1 != 0 {
-                results.push(matched_program.0);
-            }
-        }
+        metadata.save(path)
+    }
 
-        Ok(results)
+    /// Gather the details of an installed program, including its sidecar metadata.
+    pub fn get_program_info(&self, program_name: String) -> Result<ProgramInfo, Error> {
+        let program: Program = self.get_program_by_name(program_name)?;
+        let path: PathBuf = program
+            .get_program_path()
+            .ok_or_else(|| anyhow!("The program has no installed path"))?
+            .to_path_buf();
+        let bin_entry: PathBuf = get_bin_entry_path(&self.get_bin_directory()?, program.get_name());
+
+        Ok(ProgramInfo {
+            name: program.name,
+            interpreter: program.interpreter,
+            metadata: ProgramMetadata::load_or_default(&path),
+            bin_entry: bin_entry.symlink_metadata().is_ok().then_some(bin_entry),
+            path,
+        })
     }
 
     /// Returns the path to the program installation directory.
@@ -176,22 +665,23 @@ impl ProgramManager {
         self.root_directory.join("programs")
     }
 
-    /// Create a new shell script program file.
-    pub fn create_program(&self, path_to_program: &Path, program: &Program) -> Result<(), Error> {
+    /// Create a new shell script program file from a template, described by an optional comment.
+    pub fn create_program(
+        &self,
+        path_to_program: &Path,
+        program: &Program,
+        description: Option<&str>,
+        template: Option<&str>,
+    ) -> Result<(), Error> {
         if path_to_program.is_dir() {
             return Err(anyhow!(
                 "A shell script program must be a file, not a directory!"
             ));
         }
 
-        // Get the shebang based on the interpreter
-        let shebang: &str = program.interpreter.get_shebang();
-
         // Create the shell script content
-        let script_content = format!(
-            "{}\n\nmain() {{\n    echo \"Hello from {}!\"\n}}\n\nmain \"$@\"",
-            shebang, program.name
-        );
+        let script_content: String =
+            load_program_template(template, &program.name, &program.interpreter, description)?;
 
         // Create the shell script file
         match std::fs::File::create_new(path_to_program) {
@@ -222,42 +712,75 @@ impl ProgramManager {
 
         if !spm_dir.is_dir() {
             return Err(anyhow!(format!(
-                "The program installation directory `~/{}/{}` does not exist",
-                DEFAULT_SPM_FOLDER, DEFAULT_SPM_PROGRAMS_FOLDER
+                "The program installation directory `{}` does not exist",
+                spm_dir.display()
             )));
         }
 
         let mut installed_programs: Vec<Program> = Vec::new();
 
-        // Read the programs directory
-        for entry in std::fs::read_dir(spm_dir)? {
-            let entry: DirEntry = entry?;
+        // Read the programs directory, skipping the entries that cannot be read
+        // rather than failing the whole listing
+        for entry in std::fs::read_dir(&spm_dir)? {
+            let entry: DirEntry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    display_message(
+                        Level::Warn,
+                        &format!("Skipping an unreadable entry of {}: {}", spm_dir.display(), error),
+                    );
+                    continue;
+                }
+            };
             let path: PathBuf = entry.path();
 
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "sh") {
+            let is_script: bool = path.extension().is_some_and(|ext| ext == "sh");
+            if is_script && !path.exists() {
+                display_message(
+                    Level::Warn,
+                    &format!("Skipping {}: it links to a file that does not exist", path.display()),
+                );
+                continue;
+            }
+
+            if is_script && path.is_file() {
                 let program_name = path.file_stem().unwrap().to_string_lossy().to_string();
 
                 let interpreter = detect_interpreter_from_file(&path).unwrap_or(ShellType::Sh);
+                // A broken metadata file must not hide the program, nor every other one
+                let metadata: ProgramMetadata = ProgramMetadata::load_or_default(&path);
 
                 installed_programs.push(Program {
                     name: program_name,
                     path_to_program: Some(path),
                     interpreter,
+                    origin: metadata.source,
+                    source_kind: metadata.kind,
+                    version: metadata.version,
+                    installed_at: metadata.installed_at,
                 });
             }
         }
 
+        // Keep the listing, and the indexes shown by `spm list`, stable
+        installed_programs.sort_by(|a, b| a.name.cmp(&b.name));
+
         Ok(installed_programs)
     }
 
-    /// Installs a program by copying it to the program installation directory.
-    pub fn install_program(&self, path_to_program: &Path, is_force: bool) -> Result<(), Error> {
+    /// Installs a program and records where it came from.
+    fn install_program_from_source(
+        &self,
+        path_to_program: &Path,
+        resolver: &mut ConflictResolver,
+        metadata: ProgramMetadata,
+    ) -> Result<InstallOutcome, Error> {
         if !path_to_program.is_file() {
-            return Err(anyhow!("The provided path must be a .sh file"));
+            return Err(ErrorCode::UnsupportedScript.error("The provided path must be a .sh file"));
         }
 
         if path_to_program.extension().map_or(true, |ext| ext != "sh") {
-            return Err(anyhow!("Only .sh files are supported"));
+            return Err(ErrorCode::UnsupportedScript.error("Only .sh files are supported"));
         }
 
         let spm_dir: PathBuf = self.access_program_installation_directory();
@@ -272,15 +795,64 @@ impl ProgramManager {
 
         let destination = spm_dir.join(program_name);
 
+        let name: String = path_to_program
+            .file_stem()
+            .unwrap_or(program_name)
+            .to_string_lossy()
+            .to_string();
+
+        // A program that cannot run here is refused before anything changes
+        ensure_interpreter_available(
+            &detect_interpreter_from_file(path_to_program).unwrap_or(ShellType::Sh),
+        )?;
+
         // Check if this program already exists
-        if destination.exists() && !is_force {
-            return Err(anyhow!(
-                "The program already exists. Use `--force` (-F) flag to force an install or update"
-            ));
+        let is_replacing: bool = destination.exists();
+        if is_replacing && !resolver.should_overwrite(&name)? {
+            return Ok(InstallOutcome::Skipped);
+        }
+
+        let previous: ProgramMetadata = if is_replacing {
+            ProgramMetadata::load_or_default(&destination)
+        } else {
+            ProgramMetadata::default()
+        };
+
+        // Leave the installed copy alone when nothing differs
+        let is_unchanged: bool =
+            is_replacing && std::fs::read(path_to_program)? == std::fs::read(&destination)?;
+
+        // Keep the edits made to the installed copy, unless upstream changed as well
+        // and the user chooses the upstream version
+        let checksum: String = compute_sha256(path_to_program)?;
+        let installed_checksum: Option<&str> = previous
+            .checksum
+            .as_deref()
+            .filter(|_| is_replacing && !is_unchanged);
+        if let Some(installed_checksum) = installed_checksum {
+            let is_modified_locally: bool = compute_sha256(&destination)? != installed_checksum;
+            let is_changed_upstream: bool = checksum != installed_checksum;
+
+            if is_modified_locally
+                && (!is_changed_upstream
+                    || !resolver.should_take_upstream(&name, &destination, path_to_program)?)
+            {
+                // The upstream version becomes the base, so the same choice is not asked again
+                let mut kept: ProgramMetadata = previous;
+                kept.checksum = Some(checksum);
+                kept.save(&destination)?;
+                return Ok(InstallOutcome::KeptLocal);
+            }
         }
 
         // Copy the program file
-        std::fs::copy(path_to_program, &destination)?;
+        if !is_unchanged {
+            display_message(
+                Level::Debug,
+                &format!("Copying {} to {}", path_to_program.display(), destination.display()),
+            );
+            std::fs::copy(path_to_program, &destination)?;
+        }
 
         // Make sure the file is executable
         #[cfg(unix)]
@@ -291,62 +863,234 @@ impl ProgramManager {
             std::fs::set_permissions(&destination, perms)?;
         }
 
-        Ok(())
+        // Record where the program came from, keeping the settings made by the user
+        let mut metadata: ProgramMetadata = metadata;
+        metadata.checksum = Some(checksum);
+        metadata.default_args = previous.default_args;
+        metadata.is_forced = is_replacing && resolver.get_policy() == ConflictPolicy::Force;
+        metadata.installed_at = if is_unchanged && previous.installed_at.is_some() {
+            previous.installed_at
+        } else {
+            Some(get_current_timestamp())
+        };
+        metadata.save(&destination)?;
+
+        if is_unchanged {
+            Ok(InstallOutcome::Unchanged)
+        } else if is_replacing {
+            Ok(InstallOutcome::Replaced)
+        } else {
+            Ok(InstallOutcome::Installed)
+        }
     }
 
-    /// Installs all shell scripts from a Git repository.
-    pub fn install_from_git(&self, git_url: &str, is_force: bool) -> Result<(), Error> {
-        use crate::utilities::{create_temp_directory, cleanup_temp_repository, clone_git_repository};
-        
-        // Create temporary directory for cloning
-        let temp_dir = create_temp_directory()?;
-        let repo_path = temp_dir.join("repo");
-        
-        // Clone the repository
-        clone_git_repository(git_url, &repo_path)?;
-        
-        // Find all .sh files in the repository
-        let mut installed_count = 0;
-        self.install_scripts_from_directory(&repo_path, is_force, &mut installed_count)?;
-        
-        // Cleanup temporary directory
-        cleanup_temp_repository(&repo_path)?;
-        
-        if installed_count == 0 {
-            return Err(anyhow!("No shell scripts found in the repository"));
-        }
-        
-        Ok(())
-    }
-    
-    /// Recursively install all .sh files from a directory.
-    fn install_scripts_from_directory(&self, dir: &Path, is_force: bool, count: &mut usize) -> Result<(), Error> {
-        if !dir.is_dir() {
-            return Ok(());
+    /// Installs every shell script of a source: a local script, a script
+    /// downloaded from a URL, or all the scripts of a git repository.
+    pub fn install_from_source(
+        &self,
+        source: &str,
+        options: &SourceOptions,
+        resolver: &mut ConflictResolver,
+    ) -> Result<InstallSummary, Error> {
+        // Fetched files are staged in a temporary directory, removed once installed
+        let resolved: ResolvedSource =
+            while_fetching(|| select_source_resolver(source, options).resolve(source, options))?;
+        if let Some(mirror) = &resolved.metadata.mirror {
+            display_message(Level::Logging, &format!("Served by mirror: {}", mirror));
+        }
+
+        if resolved.scripts.is_empty() {
+            return Err(anyhow!(
+                "No shell scripts found in {}",
+                sanitize_for_display(&resolved.display_name)
+            ));
+        }
+
+        let source: String = resolved.display_name.clone();
+        let scripts: &[PathBuf] = &resolved.scripts;
+        let mut summary = InstallSummary::default();
+
+        let total: usize = scripts.len();
+        for (index, path) in scripts.iter().enumerate() {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let name: String = path.file_stem().unwrap().to_string_lossy().to_string();
+
+            let outcome = self.install_program_from_source(path, resolver, resolved.metadata.clone());
+            report_progress(
+                get_install_progress_event("install", &name, &outcome).with_progress(index + 1, total),
+            );
+
+            match outcome {
+                Ok(InstallOutcome::Skipped) => {
+                    display_message(Level::Logging, &format!("Skipped: {}", &file_name));
+                    summary.skipped.push(name);
+                }
+                Ok(InstallOutcome::Unchanged) => {
+                    display_message(Level::Logging, &format!("Unchanged: {}", &file_name));
+                    summary.unchanged.push(name);
+                }
+                Ok(InstallOutcome::KeptLocal) => {
+                    display_message(Level::Logging, &format!("Kept local modifications: {}", &file_name));
+                    summary.kept.push(name);
+                }
+                Ok(InstallOutcome::Replaced) => {
+                    display_message(Level::Logging, &format!("Updated: {}", &file_name));
+                    summary.modified.push(name);
+                }
+                Ok(InstallOutcome::Installed) => {
+                    display_message(Level::Logging, &format!("Installed: {}", &file_name));
+                    summary.added.push(name);
+                }
+                Err(e) => summary
+                    .failures
+                    .push(format!("Failed to install {}", file_name), e),
+            }
         }
-        
-        for entry in std::fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.is_dir() {
-                // Recursively search subdirectories
-                self.install_scripts_from_directory(&path, is_force, count)?;
-            } else if path.is_file() && path.extension().map_or(false, |ext| ext == "sh") {
-                // Install the shell script
-                match self.install_program(&path, is_force) {
-                    Ok(_) => {
-                        *count += 1;
-                        println!("Installed: {}", path.file_name().unwrap().to_string_lossy());
+
+        // Remove the programs that no longer exist upstream, when the policy in
+        // effect after the installs, which may be a "yes to all" answer, is to overwrite
+        if resolved.is_every_program && resolver.get_policy() == ConflictPolicy::Force {
+            let upstream_names: Vec<String> = scripts
+                .iter()
+                .filter_map(|path| path.file_stem())
+                .map(|name| name.to_string_lossy().to_string())
+                .collect();
+
+            for program in self.get_programs_from_source(&source)? {
+                if !upstream_names.iter().any(|name| name == program.get_name()) {
+                    match self.uninstall_program_by_name(program.get_name().to_string()) {
+                        Ok(_) => {
+                            display_message(Level::Logging, &format!("Removed: {}", program.get_name()));
+                            summary.removed.push(program.get_name().to_string());
+                        }
+                        Err(e) => summary
+                            .failures
+                            .push(format!("Failed to remove {}", program.get_name()), e),
                     }
-                    Err(e) => {
-                        eprintln!("Failed to install {}: {}", path.file_name().unwrap().to_string_lossy(), e);
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Re-fetches programs from the source recorded when they were installed and
+    /// replaces the installed copies that changed. Programs sharing a source
+    /// are updated from a single fetch.
+    pub fn update_programs(
+        &self,
+        programs: &[Program],
+        local_changes: LocalChangePolicy,
+    ) -> Vec<UpdateResult> {
+        let mut resolver =
+            ConflictResolver::new(ConflictPolicy::Force).with_local_changes(local_changes);
+        let mut results: Vec<UpdateResult> = Vec::new();
+        let mut sources: BTreeMap<(String, Option<String>, Option<SourceKind>), Vec<String>> =
+            BTreeMap::new();
+
+        for program in programs {
+            let name: String = program.get_name().to_string();
+
+            let Some(source) = program.get_origin() else {
+                results.push((
+                    name,
+                    Err(anyhow!("No source was recorded at install time, please reinstall it")),
+                ));
+                continue;
+            };
+
+            sources
+                .entry((source.to_string(), program.version.clone(), program.source_kind))
+                .or_default()
+                .push(name);
+        }
+
+        for ((source, version, kind), names) in sources {
+            match self.update_programs_from_source(&source, version.as_deref(), kind, &names, &mut resolver) {
+                Ok(outcomes) => results.extend(outcomes),
+                Err(error) => {
+                    for name in names {
+                        results.push((name, Err(anyhow!("{}", error))));
                     }
                 }
             }
         }
-        
-        Ok(())
+
+        let total: usize = results.len();
+        for (index, (name, outcome)) in results.iter().enumerate() {
+            report_progress(get_install_progress_event("update", name, outcome).with_progress(index + 1, total));
+        }
+
+        results
+    }
+
+    /// Fetches a source once and updates the named programs from it.
+    fn update_programs_from_source(
+        &self,
+        source: &str,
+        git_reference: Option<&str>,
+        kind: Option<SourceKind>,
+        names: &[String],
+        resolver: &mut ConflictResolver,
+    ) -> Result<Vec<UpdateResult>, Error> {
+        // A downloaded script is saved under the name it was installed with
+        let options =
+            SourceOptions::for_recorded_source(kind, git_reference, names.first().map(String::as_str));
+        let resolved: ResolvedSource =
+            while_fetching(|| select_source_resolver(source, &options).resolve(source, &options))?;
+
+        let outcomes = names
+            .iter()
+            .map(|name| {
+                let script: Option<&PathBuf> = resolved
+                    .scripts
+                    .iter()
+                    .find(|path| path.file_stem().is_some_and(|stem| stem.to_string_lossy() == *name));
+
+                let outcome = match script {
+                    Some(path) => self.install_program_from_source(path, resolver, resolved.metadata.clone()),
+                    None => Err(anyhow!("The program no longer exists in {}", source)),
+                };
+                (name.clone(), outcome)
+            })
+            .collect();
+
+        Ok(outcomes)
+    }
+
+    /// Retrieves the installed programs recorded with the given source.
+    /// The source can be a repository URL or a trailing part of it, like `user/repo`,
+    /// as long as a single source ends with it.
+    pub fn get_programs_from_source(&self, source: &str) -> Result<Vec<Program>, Error> {
+        let source: String = normalize_source_id(source);
+        let suffix: String = format!("/{}", source);
+        let programs: Vec<Program> = self.get_installed_programs()?;
+        let get_source_id = |program: &Program| program.get_origin().map(normalize_source_id);
+
+        if programs.iter().any(|program| get_source_id(program).as_ref() == Some(&source)) {
+            return Ok(programs
+                .into_iter()
+                .filter(|program| get_source_id(program).as_ref() == Some(&source))
+                .collect());
+        }
+
+        let matched: Vec<Program> = programs
+            .into_iter()
+            .filter(|program| get_source_id(program).is_some_and(|origin| origin.ends_with(&suffix)))
+            .collect();
+        let mut candidates: Vec<String> = matched.iter().filter_map(get_source_id).collect();
+        candidates.sort();
+        candidates.dedup();
+
+        if candidates.len() > 1 {
+            return Err(anyhow!(
+                "'{}' matches several sources, give the one you mean in full: {}",
+                source,
+                candidates.join(", ")
+            ));
+        }
+
+        Ok(matched)
     }
 
     /// Uninstalls a program by removing it from the installation directory.
@@ -358,6 +1102,9 @@ impl ProgramManager {
         std::fs::remove_file(path_to_program)
             .map_err(|e| anyhow!("Failed to remove program file: {}", e))?;
 
+        // Remove the sidecar metadata along with the program
+        ProgramMetadata::default().save(path_to_program)?;
+
         Ok(())
     }
 
@@ -365,10 +1112,443 @@ impl ProgramManager {
         let program: Program = self.get_program_by_name(program_name)?;
         let program_path = program.get_program_path()
             .ok_or_else(|| anyhow!("Program path not available"))?;
-        self.uninstall_program(Path::new(program_path))
+        self.uninstall_program(program_path)?;
+
+        // Remove the bin entry of the program rather than leave it dangling
+        let bin_entry: PathBuf =
+            get_bin_entry_path(&self.root_directory.join(DEFAULT_SPM_BIN_FOLDER), program.get_name());
+        if is_bin_entry_current(&bin_entry, program_path) {
+            std::fs::remove_file(&bin_entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The file listing the paths of a source that spm never installs
+const IGNORE_FILE_NAME: &str = ".spmignore";
+
+/// Glob patterns read from a `.spmignore` file, one per line like `.gitignore`.
+/// A pattern with a slash matches from the root of the source, one without
+/// matches the name of any file or directory, and a trailing slash only
+/// matches directories. `*` and `?` stay within a path segment, `**` does not.
+#[derive(Debug, Default, Clone)]
+pub struct IgnoreRules {
+    patterns: Vec<String>,
+}
+
+impl IgnoreRules {
+    /// Load the `.spmignore` file at the root of a source, if there is one
+    pub fn load(root: &Path) -> Result<Self, Error> {
+        let path: PathBuf = root.join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let patterns: Vec<String> = std::fs::read_to_string(&path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+
+        Ok(Self { patterns })
+    }
+
+    /// Whether a path, relative to the root of the source, is ignored.
+    /// The `.git` directory always is.
+    pub fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let path: String = relative_path.to_string_lossy().replace('\\', "/");
+        let name: &str = path.rsplit('/').next().unwrap_or(&path);
+        if is_dir && name == ".git" {
+            return true;
+        }
+
+        self.patterns.iter().any(|pattern| {
+            let (pattern, is_dir_only) = match pattern.strip_suffix('/') {
+                Some(pattern) => (pattern, true),
+                None => (pattern.as_str(), false),
+            };
+            if is_dir_only && !is_dir {
+                return false;
+            }
+
+            match pattern.strip_prefix('/') {
+                Some(anchored) => matches_glob(anchored.as_bytes(), path.as_bytes()),
+                None if pattern.contains('/') => matches_glob(pattern.as_bytes(), path.as_bytes()),
+                None => matches_glob(pattern.as_bytes(), name.as_bytes()),
+            }
+        })
     }
 }
 
+/// Match a text against a glob pattern with `*`, `?` and `**`
+fn matches_glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            let rest_after_slash: &[u8] = rest.strip_prefix(b"/").unwrap_or(rest);
+            matches_glob(rest_after_slash, text)
+                || (0..=text.len()).any(|start| matches_glob(rest, &text[start..]))
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&end| !text[..end].contains(&b'/'))
+            .any(|end| matches_glob(rest, &text[end..])),
+        [b'?', rest @ ..] => {
+            matches!(text, [first, tail @ ..] if *first != b'/' && matches_glob(rest, tail))
+        }
+        [first, rest @ ..] => {
+            matches!(text, [head, tail @ ..] if head == first && matches_glob(rest, tail))
+        }
+    }
+}
+
+/// Recursively collect all .sh files from a directory, leaving out the
+/// paths listed in its `.spmignore` file.
+pub fn collect_scripts_from_directory(dir: &Path, scripts: &mut Vec<PathBuf>) -> Result<(), Error> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    collect_scripts(dir, dir, &IgnoreRules::load(dir)?, scripts)
+}
+
+fn collect_scripts(
+    root: &Path,
+    dir: &Path,
+    rules: &IgnoreRules,
+    scripts: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry: DirEntry = entry?;
+        let path: PathBuf = entry.path();
+
+        // Never follow symlinks or Windows junctions, they may loop back into the tree
+        if is_link_or_reparse_point(&entry)? {
+            continue;
+        }
+
+        let is_dir: bool = path.is_dir();
+        if rules.is_ignored(path.strip_prefix(root).unwrap_or(&path), is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            // Recursively search subdirectories
+            collect_scripts(root, &path, rules, scripts)?;
+        } else if path.is_file() && path.extension().is_some_and(|ext| ext == "sh") {
+            scripts.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether a directory entry is a symlink, a junction or another reparse point
+fn is_link_or_reparse_point(entry: &DirEntry) -> Result<bool, Error> {
+    if entry.file_type()?.is_symlink() {
+        return Ok(true);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+        if entry.metadata()?.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0 {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Returns the path of the sidecar metadata file of a program
+fn get_metadata_path(path_to_program: &Path) -> PathBuf {
+    path_to_program.with_extension("json")
+}
+
+/// The templates built into `spm new`, the first one being the default
+pub const PROGRAM_TEMPLATES: [&str; 2] = ["default", "minimal"];
+
+/// Render a built-in template, or a script on disk whose `{{name}}`,
+/// `{{interpreter}}` and `{{description}}` placeholders are filled in
+fn load_program_template(
+    template: Option<&str>,
+    name: &str,
+    interpreter: &ShellType,
+    description: Option<&str>,
+) -> Result<String, Error> {
+    // The description is a single comment line under the shebang
+    let description: Option<&str> = description
+        .and_then(|description| description.lines().next())
+        .map(str::trim)
+        .filter(|description| !description.is_empty());
+
+    match template.unwrap_or(PROGRAM_TEMPLATES[0]) {
+        "default" => Ok(render_program_template(name, interpreter, description)),
+        "minimal" => Ok(render_minimal_template(name, interpreter, description)),
+        path => {
+            let path: &Path = Path::new(path);
+            if !path.is_file() {
+                return Err(anyhow!(
+                    "No template named {}. Use {} or the path of a script",
+                    path.display(),
+                    PROGRAM_TEMPLATES.join(", ")
+                ));
+            }
+
+            Ok(std::fs::read_to_string(path)?
+                .replace("{{name}}", name)
+                .replace("{{interpreter}}", &interpreter.to_string())
+                .replace("{{description}}", description.unwrap_or_default()))
+        }
+    }
+}
+
+/// The `minimal` template: a shebang and a greeting, nothing else
+fn render_minimal_template(name: &str, interpreter: &ShellType, description: Option<&str>) -> String {
+    let greeting: String = format!("Hello from {}!", name);
+
+    match interpreter {
+        ShellType::Cmd => format!(
+            "{}\r\n{}@echo off\r\necho {}\r\n",
+            interpreter.get_shebang(),
+            description.map_or(String::new(), |description| format!("rem {}\r\n", description)),
+            greeting
+        ),
+        _ => format!(
+            "{}\n{}\necho {}\n",
+            interpreter.get_shebang(),
+            description.map_or(String::new(), |description| format!("# {}\n", description)),
+            shell_words::quote(&greeting)
+        ),
+    }
+}
+
+/// The script written by `spm new`, which runs in strict mode and passes `spm check`
+fn render_program_template(name: &str, interpreter: &ShellType, description: Option<&str>) -> String {
+    let strict_mode: &str = match interpreter {
+        ShellType::Bash | ShellType::Zsh => {
+            "# Stop at the first failing command, unset variable or failing pipe\nset -euo pipefail\n"
+        }
+        ShellType::Sh => "# Stop at the first failing command or unset variable\nset -eu\n",
+        // Batch files have neither functions nor a strict mode, the shebang
+        // only tells spm which interpreter to use
+        ShellType::Cmd => {
+            return format!(
+                "{}\r\n{}@echo off\r\nrem When run by spm, %SPM_PROGRAM_PATH% is the path of this script\r\necho Hello from {}!\r\n",
+                interpreter.get_shebang(),
+                description.map_or(String::new(), |description| format!("rem {}\r\n", description)),
+                name
+            );
+        }
+    };
+    let usage: String = format!("Usage: {} [arguments]", name);
+    let greeting: String = format!("Hello from {}!", name);
+
+    format!(
+        "{shebang}\n\
+         {description}\
+         {strict_mode}\
+         \n\
+         # When run by spm, the script can locate itself through these variables:\n\
+         #   $SPM_PROGRAM_NAME  the name of the program\n\
+         #   $SPM_PROGRAM_PATH  the absolute path of this script\n\
+         #   $SPM_PROGRAM_DIR   the absolute path of the directory holding it\n\
+         #   $SPM_HOME          the root directory of spm\n\
+         \n\
+         usage() {{\n    echo {usage}\n}}\n\
+         \n\
+         main() {{\n    \
+         if [ \"${{1:-}}\" = \"-h\" ] || [ \"${{1:-}}\" = \"--help\" ]; then\n        \
+         usage\n        \
+         return 0\n    \
+         fi\n\
+         \n    \
+         echo {greeting}\n\
+         }}\n\
+         \n\
+         main \"$@\"\n",
+        shebang = interpreter.get_shebang(),
+        description = description.map_or(String::new(), |description| format!("# {}\n\n", description)),
+        strict_mode = strict_mode,
+        usage = shell_words::quote(&usage),
+        greeting = shell_words::quote(&greeting),
+    )
+}
+
+/// Show the differences between the installed copy of a program and the upstream one
+fn display_diff(installed: &Path, upstream: &Path) -> Result<(), Error> {
+    let installed_content: String = std::fs::read_to_string(installed)?;
+    let upstream_content: String = std::fs::read_to_string(upstream)?;
+
+    println!("--- installed");
+    println!("+++ upstream");
+    for line in diff_lines(&installed_content, &upstream_content) {
+        println!("{}", sanitize_for_display(&line));
+    }
+
+    Ok(())
+}
+
+/// Compare two texts line by line, prefixing removed lines with `-`,
+/// added lines with `+` and common lines with a space.
+/// Scripts are small, so the longest common subsequence is computed in full.
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // lengths[i][j] is the longest common subsequence of old_lines[i..] and new_lines[j..]
+    let mut lengths: Vec<Vec<usize>> = vec![vec![0; new_lines.len() + 1]; old_lines.len() + 1];
+    for i in (0..old_lines.len()).rev() {
+        for j in (0..new_lines.len()).rev() {
+            lengths[i][j] = if old_lines[i] == new_lines[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut output: Vec<String> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_lines.len() && j < new_lines.len() {
+        if old_lines[i] == new_lines[j] {
+            output.push(format!(" {}", old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            output.push(format!("-{}", old_lines[i]));
+            i += 1;
+        } else {
+            output.push(format!("+{}", new_lines[j]));
+            j += 1;
+        }
+    }
+    output.extend(old_lines[i..].iter().map(|line| format!("-{}", line)));
+    output.extend(new_lines[j..].iter().map(|line| format!("+{}", line)));
+
+    output
+}
+
+/// Seconds since the Unix epoch, recorded when a program is installed
+fn get_current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Normalize a repository URL into the identifier recorded as a program source
+pub fn normalize_source_id(git_url: &str) -> String {
+    let trimmed: &str = git_url.trim().trim_end_matches('/');
+    trimmed.strip_suffix(".git").unwrap_or(trimmed).to_string()
+}
+
+/// Returns the path of the bin entry that exposes a program
+fn get_bin_entry_path(bin_directory: &Path, program_name: &str) -> PathBuf {
+    if cfg!(windows) {
+        bin_directory.join(format!("{}.cmd", program_name))
+    } else {
+        bin_directory.join(program_name)
+    }
+}
+
+/// Render the wrapper used on platforms without symlinks
+#[cfg(not(unix))]
+fn render_bin_wrapper(target: &Path) -> String {
+    format!(
+        "@echo off\r\n{}\r\n\"{}\" %*\r\n",
+        crate::properties::BIN_WRAPPER_MARKER,
+        target.display()
+    )
+}
+
+/// Check whether a bin entry already points at the given program file
+fn is_bin_entry_current(entry: &Path, target: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        std::fs::read_link(entry).is_ok_and(|linked| linked == target)
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::read_to_string(entry).is_ok_and(|content| content == render_bin_wrapper(target))
+    }
+}
+
+/// Check whether a bin entry was created by spm, pointing into the programs
+/// directory. The target does not need to exist, so that broken entries are found.
+fn is_managed_bin_entry(entry: &Path, programs_directory: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        std::fs::read_link(entry).is_ok_and(|target| target.starts_with(programs_directory))
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::read_to_string(entry).is_ok_and(|content| {
+            content.contains(crate::properties::BIN_WRAPPER_MARKER)
+                && content.contains(&programs_directory.display().to_string())
+        })
+    }
+}
+
+/// Point a bin entry at a program file, counting what changed in the report.
+/// A file of the same name that spm did not create is left alone.
+fn link_bin_entry(
+    entry: &Path,
+    target: &Path,
+    programs_directory: &Path,
+    report: &mut RelinkReport,
+) -> Result<(), Error> {
+    if is_bin_entry_current(entry, target) {
+        report.unchanged += 1;
+        return Ok(());
+    }
+
+    let existed: bool = entry.symlink_metadata().is_ok();
+    if existed && !is_managed_bin_entry(entry, programs_directory) {
+        display_message(
+            Level::Warn,
+            &format!("Left {} alone, it was not created by spm", entry.display()),
+        );
+        return Ok(());
+    }
+    write_bin_entry(entry, target)?;
+
+    if existed {
+        report.updated += 1;
+    } else {
+        report.created += 1;
+    }
+
+    Ok(())
+}
+
+/// Write a bin entry through a temporary file and an atomic rename, so that
+/// concurrent readers never observe a missing or half-written entry.
+fn write_bin_entry(entry: &Path, target: &Path) -> Result<(), Error> {
+    let file_name: String = entry
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid bin entry name"))?
+        .to_string_lossy()
+        .to_string();
+    let temporary_entry: PathBuf =
+        entry.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    if temporary_entry.symlink_metadata().is_ok() {
+        std::fs::remove_file(&temporary_entry)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, &temporary_entry)?;
+    #[cfg(not(unix))]
+    std::fs::write(&temporary_entry, render_bin_wrapper(target))?;
+
+    std::fs::rename(&temporary_entry, entry)?;
+
+    Ok(())
+}
+
 /// Detect the interpreter from the shebang line of a shell script file
 fn detect_interpreter_from_file(file_path: &Path) -> Result<ShellType, Error> {
     let content = std::fs::read_to_string(file_path)?;
@@ -390,30 +1570,180 @@ fn detect_interpreter_from_file(file_path: &Path) -> Result<ShellType, Error> {
     Ok(ShellType::Sh)
 }
 
-/// Normalize a program name
-pub fn normalize_program_name(name: &str) -> String {
-    let standardized_separator: &str = "-";
+/// Split comma separated keywords into their normalized form, compared with
+/// program names, and their lowercase form, compared with origins
+fn parse_search_keywords(keywords: &str) -> Vec<(String, String)> {
+    keywords
+        .split(",")
+        .filter_map(|keyword: &str| {
+            normalize_program_name(keyword)
+                .ok()
+                .map(|word| (word, keyword.trim().to_lowercase()))
+        })
+        .collect()
+}
 
-    // Replace underscores with hyphens
-    let mut normalized_name = name.replace("_", standardized_separator);
+/// Score how well a program matches the keywords, 0 meaning no match.
+/// Names are compared normalized, so that `my_tool` finds `MyTool`,
+/// while origins are matched as they are written.
+fn get_match_score(
+    program: &Program,
+    words: &[(String, String)],
+    weights: &SearchWeights,
+    is_origin_included: bool,
+) -> usize {
+    let program_name: String = normalize_program_name(program.get_name()).unwrap_or_default();
+    let program_origin: String = program.get_origin().unwrap_or_default().to_lowercase();
+    let mut match_score: usize = 0;
+
+    for (word, raw_word) in words {
+        if program_name == *word {
+            // When the keyword is the program name
+            match_score += weights.exact;
+        } else if program_name.contains(word) {
+            // When a keyword is found in the name
+            match_score += weights.name;
+        }
 
-    // Replace uppercase letters with lowercase prefixed by a hyphen
-    normalized_name = normalized_name
-        .chars()
-        .flat_map(|c| {
-            if c.is_uppercase() {
-                vec![
-                    standardized_separator.to_string(),
-                    c.to_lowercase().to_string(),
-                ]
-            } else {
-                vec![c.to_string()]
+        // When a keyword is found in where the program came from
+        if is_origin_included && program_origin.contains(raw_word.as_str()) {
+            match_score += weights.origin;
+        }
+    }
+
+    match_score
+}
+
+/// Normalize a program name into lowercase words joined by hyphens, so that
+/// `My_HTTPTool`, `my-http-tool` and `my http tool` compare equal. Runs of
+/// separators collapse into one and an acronym stays a single word.
+pub fn normalize_program_name(name: &str) -> Result<String, Error> {
+    let characters: Vec<char> = name.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut word: String = String::new();
+
+    for (index, &c) in characters.iter().enumerate() {
+        if c == '_' || c == '-' || c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
             }
-        })
-        .collect::<String>();
+            continue;
+        }
+
+        // A capital starts a word after a lowercase letter or a digit, and
+        // the last capital of an acronym starts the word that follows it
+        if c.is_uppercase() && !word.is_empty() {
+            let previous: char = characters[index - 1];
+            let is_next_lowercase: bool = characters
+                .get(index + 1)
+                .is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && is_next_lowercase)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        word.extend(c.to_lowercase());
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    if words.is_empty() {
+        return Err(anyhow!("'{}' is not a valid program name", name));
+    }
+
+    Ok(words.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines_marks_removed_added_and_common_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nx\nc\nd\n"),
+            vec![" a", "-b", "+x", " c", "+d"]
+        );
+    }
+
+    fn program_from(name: &str, origin: Option<&str>) -> Program {
+        let mut program = Program::new(name.to_string(), ShellType::Sh);
+        program.origin = origin.map(str::to_string);
+        program
+    }
+
+    #[test]
+    fn match_scores_follow_the_weights() {
+        let weights = SearchWeights {
+            exact: 10,
+            name: 3,
+            origin: 1,
+        };
+        let score = |name: &str, keywords: &str, is_origin_included: bool| {
+            let program: Program = program_from(name, Some("https://github.com/owner/Tools"));
+            get_match_score(&program, &parse_search_keywords(keywords), &weights, is_origin_included)
+        };
+
+        assert_eq!(score("backup", "backup", false), 10);
+        assert_eq!(score("backup-db", "backup", false), 3);
+        assert_eq!(score("MyTool", "my_tool", false), 10);
+        assert_eq!(score("deploy", "backup", false), 0);
 
-    // Remove leading hyphen if present
-    normalized_name
-        .trim_start_matches(standardized_separator)
-        .to_string()
+        // Every keyword adds up, and origins only count when asked for
+        assert_eq!(score("backup-db", "backup, db", false), 6);
+        assert_eq!(score("deploy", "owner/tools", false), 0);
+        assert_eq!(score("deploy", "owner/tools", true), 1);
+    }
+
+    #[test]
+    fn program_names_normalize_into_hyphenated_words() {
+        let cases: [(&str, &str); 14] = [
+            ("backup", "backup"),
+            ("My_HTTPTool", "my-http-tool"),
+            ("my-http-tool", "my-http-tool"),
+            ("my http tool", "my-http-tool"),
+            ("MyTool", "my-tool"),
+            ("HTTPServer", "http-server"),
+            ("HTTP", "http"),
+            ("tool2Go", "tool2-go"),
+            ("v2", "v2"),
+            // Runs of separators collapse into one
+            ("my--tool", "my-tool"),
+            ("my _-\ttool", "my-tool"),
+            // Separators around the name are trimmed
+            ("__tool__", "tool"),
+            (" -tool- ", "tool"),
+            ("-My_Tool-", "my-tool"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(normalize_program_name(name).unwrap(), expected, "{}", name);
+        }
+
+        // Names without a single word are rejected
+        for name in ["", "   ", "-_-", "\t\n"] {
+            assert!(normalize_program_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn default_weights_favor_exact_names() {
+        let weights = SearchWeights::default();
+        let words: Vec<(String, String)> = parse_search_keywords("backup");
+        assert!(
+            get_match_score(&program_from("backup", None), &words, &weights, false)
+                > get_match_score(&program_from("backup-db", None), &words, &weights, false)
+        );
+    }
+
+    #[test]
+    fn diff_lines_handles_empty_sides() {
+        assert_eq!(diff_lines("", "a\n"), vec!["+a"]);
+        assert_eq!(diff_lines("a\n", ""), vec!["-a"]);
+        assert!(diff_lines("", "").is_empty());
+    }
 }
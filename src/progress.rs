@@ -0,0 +1,93 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Error, Result};
+use serde::Serialize;
+
+/// A step of a long operation, reported to tools that wrap spm
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    /// The kind of work being done, e.g. `clone`, `download` or `install`
+    pub phase: &'static str,
+    /// The program the event is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// How far the phase is, from 0 to 100, when it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<u8>,
+    pub message: String,
+}
+
+impl ProgressEvent {
+    pub fn new(phase: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            phase,
+            program: None,
+            percent: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Set the percentage from a count of completed items
+    pub fn with_progress(mut self, completed: usize, total: usize) -> Self {
+        self.percent = (completed.min(total) * 100)
+            .checked_div(total)
+            .map(|percent| percent as u8);
+        self
+    }
+}
+
+/// Receives the progress events of spm
+pub trait ProgressReporter: Send + Sync {
+    fn report(&self, event: &ProgressEvent);
+}
+
+/// Writes each event as a line of JSON
+pub struct JsonLinesReporter {
+    output: Mutex<File>,
+}
+
+impl JsonLinesReporter {
+    /// Write the events to a file, which may also be a descriptor such as `/dev/fd/3`
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            output: Mutex::new(File::create(path)?),
+        })
+    }
+}
+
+impl ProgressReporter for JsonLinesReporter {
+    fn report(&self, event: &ProgressEvent) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+
+        // Progress is best effort and must never fail the operation itself
+        if let Ok(mut output) = self.output.lock() {
+            let _ = writeln!(output, "{}", line).and_then(|_| output.flush());
+        }
+    }
+}
+
+static REPORTER: OnceLock<Box<dyn ProgressReporter>> = OnceLock::new();
+
+/// Send the progress events of this invocation to a reporter
+pub fn set_progress_reporter(reporter: Box<dyn ProgressReporter>) {
+    let _ = REPORTER.set(reporter);
+}
+
+/// Report an event, when anything listens to them
+pub fn report_progress(event: ProgressEvent) {
+    if let Some(reporter) = REPORTER.get() {
+        reporter.report(&event);
+    }
+}
@@ -1,3 +1,13 @@
 pub static DEFAULT_SPM_FOLDER: &str = ".spm";
+pub static SPM_HOME_ENVIRONMENT_VARIABLE: &str = "SPM_HOME";
+pub static PROGRAM_NAME_ENVIRONMENT_VARIABLE: &str = "SPM_PROGRAM_NAME";
+pub static PROGRAM_PATH_ENVIRONMENT_VARIABLE: &str = "SPM_PROGRAM_PATH";
+pub static PROGRAM_DIRECTORY_ENVIRONMENT_VARIABLE: &str = "SPM_PROGRAM_DIR";
 pub static DEFAULT_SPM_PROGRAMS_FOLDER: &str = "programs";
 pub static DEFAULT_TEMPORARY_FOLDER: &str = "tmp";
+pub static DEFAULT_CACHE_FOLDER: &str = "cache";
+pub static DEFAULT_LOCK_FILE: &str = "spm.lock";
+pub static DEFAULT_CONFIGURATION_FILE: &str = "config.json";
+pub static DEFAULT_SPM_BIN_FOLDER: &str = "bin";
+#[cfg(not(unix))]
+pub static BIN_WRAPPER_MARKER: &str = ":: spm-wrapper v1";
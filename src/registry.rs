@@ -0,0 +1,207 @@
+use std::{cmp::Ordering, collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Error, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::{Configuration, Registry};
+use crate::display_control::{Level, display_message};
+use crate::utilities::{create_temp_directory, download_file, is_single_file_url};
+
+/// A program listed in the index of a registry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RegistryEntry {
+    /// Where the program is installed from: a git repository or the URL of a script
+    pub url: String,
+    /// The tags or branches of the repository that can be installed
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+impl RegistryEntry {
+    /// The highest of the listed versions, comparing the numbers in them
+    pub fn get_latest_version(&self) -> Option<&str> {
+        self.versions
+            .iter()
+            .max_by(|a, b| compare_versions(a, b))
+            .map(String::as_str)
+    }
+
+    /// The source to install, at the given version or the latest one
+    pub fn get_source(&self, version: Option<&str>) -> String {
+        match version.or(self.get_latest_version()) {
+            // A single script has no versions to check out
+            Some(version) if !is_single_file_url(&self.url) => format!("{}@{}", self.url, version),
+            _ => self.url.clone(),
+        }
+    }
+}
+
+/// The index of a registry, keyed by program name
+pub type RegistryIndex = BTreeMap<String, RegistryEntry>;
+
+/// A program found by searching the registries
+#[derive(Debug, Clone)]
+pub struct RegistryMatch {
+    pub registry: String,
+    pub name: String,
+    pub entry: RegistryEntry,
+}
+
+/// Order versions by the numbers in them, so that `v1.10` comes after `v1.9`
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+
+    numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+}
+
+/// Read the index of a registry, over HTTP or from a local file
+pub fn fetch_registry_index(url: &str) -> Result<RegistryIndex, Error> {
+    let content: String = if url.starts_with("http://") || url.starts_with("https://") {
+        let path: PathBuf = create_temp_directory()?.join("registry-index.json");
+        download_file(url, &path)?;
+        let content = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+        content?
+    } else {
+        let path: &str = url.strip_prefix("file://").unwrap_or(url);
+        std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?
+    };
+
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse the index of {}: {}", url, e))
+}
+
+/// Fetch the index of every registry, warning about the ones that fail
+fn fetch_registry_indexes(registries: &[Registry]) -> Vec<(&Registry, RegistryIndex)> {
+    registries
+        .iter()
+        .filter_map(|registry| match fetch_registry_index(&registry.url) {
+            Ok(index) => Some((registry, index)),
+            Err(error) => {
+                display_message(
+                    Level::Warn,
+                    &format!("Skipping registry {}: {}", registry.name, error),
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a source is a bare program name, optionally with `@<version>`,
+/// rather than a path or a URL
+fn is_program_name(source: &str) -> bool {
+    let name: &str = source.split('@').next().unwrap_or(source);
+
+    !name.is_empty()
+        && !name.ends_with(".sh")
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        && !std::path::Path::new(source).exists()
+}
+
+/// Turn `name` or `name@version` into the source the registries list for it,
+/// trying them in order. Returns `None` for anything else than a program name,
+/// or when no registry is configured.
+pub fn resolve_registry_source(source: &str) -> Result<Option<(String, String)>, Error> {
+    let registries: Vec<Registry> = Configuration::load()?.registries;
+    if registries.is_empty() || !is_program_name(source) {
+        return Ok(None);
+    }
+
+    let (name, version) = match source.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (source, None),
+    };
+
+    for (registry, index) in fetch_registry_indexes(&registries) {
+        if let Some(entry) = index.get(name) {
+            return Ok(Some((registry.name.clone(), entry.get_source(version))));
+        }
+    }
+
+    Err(anyhow!(
+        "No registry lists a program named {} (searched: {})",
+        name,
+        registries
+            .iter()
+            .map(|registry| registry.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    ))
+}
+
+/// Find the programs of the registries whose names contain any of the keywords
+pub fn search_registries(keywords: &[String]) -> Result<Vec<RegistryMatch>, Error> {
+    let registries: Vec<Registry> = Configuration::load()?.registries;
+    let keywords: Vec<String> = keywords.iter().map(|keyword| keyword.to_lowercase()).collect();
+    let mut matches: Vec<RegistryMatch> = Vec::new();
+
+    for (registry, index) in fetch_registry_indexes(&registries) {
+        for (name, entry) in index {
+            let lowercase_name: String = name.to_lowercase();
+            if keywords.iter().any(|keyword| lowercase_name.contains(keyword.as_str())) {
+                matches.push(RegistryMatch {
+                    registry: registry.name.clone(),
+                    name,
+                    entry,
+                });
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Add a registry after checking that its index can be read, or change the
+/// url of the registry with the same name. Returns whether it replaced one.
+pub fn add_registry(name: &str, url: &str) -> Result<bool, Error> {
+    let index: RegistryIndex = fetch_registry_index(url)?;
+    if index.is_empty() {
+        display_message(Level::Warn, &format!("The index of {} lists no programs", name));
+    }
+
+    let mut configuration: Configuration = Configuration::load()?;
+    let registry = Registry {
+        name: name.to_string(),
+        url: url.to_string(),
+    };
+
+    let is_replaced: bool = match configuration
+        .registries
+        .iter_mut()
+        .find(|registry| registry.name == name)
+    {
+        Some(existing) => {
+            *existing = registry;
+            true
+        }
+        None => {
+            configuration.registries.push(registry);
+            false
+        }
+    };
+    configuration.save()?;
+
+    Ok(is_replaced)
+}
+
+/// Remove a registry by name
+pub fn remove_registry(name: &str) -> Result<(), Error> {
+    let mut configuration: Configuration = Configuration::load()?;
+    let count: usize = configuration.registries.len();
+    configuration.registries.retain(|registry| registry.name != name);
+
+    if configuration.registries.len() == count {
+        return Err(anyhow!("No registry named {}", name));
+    }
+
+    configuration.save()
+}
@@ -0,0 +1,370 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Error, Result, anyhow};
+
+/// Marker appended to the crontab lines managed by spm, followed by the program name
+const SCHEDULE_MARKER: &str = "# spm-schedule:";
+
+/// Shorthands accepted by cron in place of the five time fields
+const CRON_SHORTHANDS: [&str; 8] = [
+    "@reboot", "@yearly", "@annually", "@monthly", "@weekly", "@daily", "@midnight", "@hourly",
+];
+
+/// Check that a cron expression has five time fields, or is a cron shorthand
+pub fn validate_cron_expression(expression: &str) -> Result<(), Error> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let is_valid: bool = match fields.as_slice() {
+        [shorthand] => CRON_SHORTHANDS.contains(shorthand),
+        fields => {
+            fields.len() == 5
+                && fields.iter().all(|field| {
+                    field
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "*/,-".contains(c))
+                })
+        }
+    };
+
+    if !is_valid {
+        return Err(anyhow!(
+            "Invalid cron expression '{}'. Use five fields such as \"0 3 * * *\", or a shorthand like @daily",
+            expression
+        ));
+    }
+
+    Ok(())
+}
+
+/// Build the crontab line that runs a program with the environment spm needs
+pub fn render_schedule_entry(
+    expression: &str,
+    spm_executable: &Path,
+    spm_home: &Path,
+    program_name: &str,
+    args: &[String],
+) -> String {
+    let path: String = std::env::var("PATH").unwrap_or_default();
+    let mut command: Vec<String> = vec![
+        format!("PATH={}", shell_words::quote(&path)),
+        format!("SPM_HOME={}", shell_words::quote(&spm_home.display().to_string())),
+        shell_words::quote(&spm_executable.display().to_string()).to_string(),
+        "run".to_string(),
+        "--quiet".to_string(),
+        "--no-fancy".to_string(),
+        shell_words::quote(program_name).to_string(),
+    ];
+    command.extend(args.iter().map(|arg| shell_words::quote(arg).to_string()));
+
+    // Cron turns an unescaped % of the command into a newline
+    format!(
+        "{} {} {} {}",
+        expression.trim(),
+        command.join(" ").replace('%', "\\%"),
+        SCHEDULE_MARKER,
+        program_name
+    )
+}
+
+/// Returns the program name of a line managed by spm
+fn get_scheduled_program(line: &str) -> Option<&str> {
+    line.rsplit_once(SCHEDULE_MARKER)
+        .map(|(_, name)| name.trim())
+}
+
+/// List the lines managed by spm, with the program each one runs
+pub fn list_schedule_entries(crontab: &str) -> Vec<(String, String)> {
+    crontab
+        .lines()
+        .filter_map(|line| {
+            get_scheduled_program(line).map(|name| (name.to_string(), line.to_string()))
+        })
+        .collect()
+}
+
+/// Remove the lines that spm manages for a program, leaving any other line alone.
+/// Returns the new crontab and how many lines were removed.
+pub fn remove_schedule_entries(crontab: &str, program_name: &str) -> (String, usize) {
+    let mut removed: usize = 0;
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in crontab.lines() {
+        if get_scheduled_program(line) == Some(program_name) {
+            removed += 1;
+        } else {
+            lines.push(line);
+        }
+    }
+
+    (join_crontab_lines(&lines), removed)
+}
+
+/// Add the line of a program, replacing the one spm previously managed for it
+pub fn add_schedule_entry(crontab: &str, program_name: &str, entry: &str) -> String {
+    let (crontab, _) = remove_schedule_entries(crontab, program_name);
+    let mut lines: Vec<&str> = crontab.lines().collect();
+    lines.push(entry);
+
+    join_crontab_lines(&lines)
+}
+
+/// Cron requires the last line to end with a newline
+fn join_crontab_lines(lines: &[&str]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    format!("{}\n", lines.join("\n"))
+}
+
+/// Read the crontab of the current user, which is empty when there is none
+pub fn read_crontab() -> Result<String, Error> {
+    let output = Command::new("crontab")
+        .arg("-l")
+        .output()
+        .map_err(|e| anyhow!("Failed to run crontab, is cron installed? {}", e))?;
+
+    // crontab exits with 1 when the user has no crontab yet
+    if !output.status.success() {
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_lowercase();
+        if stderr.contains("no crontab") {
+            return Ok(String::new());
+        }
+        return Err(anyhow!("Failed to read the crontab: {}", stderr.trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Replace the crontab of the current user
+pub fn write_crontab(crontab: &str) -> Result<(), Error> {
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run crontab, is cron installed? {}", e))?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to write to crontab"))?
+        .write_all(crontab.as_bytes())?;
+
+    if !child.wait()?.success() {
+        return Err(anyhow!("crontab refused the new schedule"));
+    }
+
+    Ok(())
+}
+
+/// The Task Scheduler command to use instead of cron on Windows.
+/// Only the expressions that map to a Task Scheduler schedule are accepted.
+pub fn render_task_scheduler_command(
+    expression: &str,
+    spm_executable: &Path,
+    program_name: &str,
+    args: &[String],
+) -> Result<String, Error> {
+    let schedule: Vec<String> = get_task_scheduler_schedule(expression)?;
+
+    let mut command: Vec<String> = vec![
+        quote_windows_argument(&spm_executable.display().to_string()),
+        "run".to_string(),
+        "--quiet".to_string(),
+        "--no-fancy".to_string(),
+        quote_windows_argument(program_name),
+    ];
+    command.extend(args.iter().map(|arg| quote_windows_argument(arg)));
+
+    let mut schtasks: Vec<String> = vec![
+        "schtasks".to_string(),
+        "/Create".to_string(),
+        "/TN".to_string(),
+        quote_windows_argument(&format!("spm-{}", program_name)),
+        "/TR".to_string(),
+        quote_windows_argument(&command.join(" ")),
+    ];
+    schtasks.extend(schedule);
+
+    Ok(schtasks.join(" "))
+}
+
+/// Translate a cron expression into the `/SC` options of schtasks
+fn get_task_scheduler_schedule(expression: &str) -> Result<Vec<String>, Error> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let is_number = |field: &str, max: u32| field.parse::<u32>().is_ok_and(|value| value <= max);
+    let time = |hour: &str, minute: &str| format!("{:0>2}:{:0>2}", hour, minute);
+
+    let schedule: Option<String> = match fields.as_slice() {
+        ["@reboot"] => Some("/SC ONSTART".to_string()),
+        ["@hourly"] => Some("/SC HOURLY /ST 00:00".to_string()),
+        ["@daily" | "@midnight"] => Some("/SC DAILY /ST 00:00".to_string()),
+        ["@weekly"] => Some("/SC WEEKLY /D SUN /ST 00:00".to_string()),
+        ["@monthly"] => Some("/SC MONTHLY /D 1 /ST 00:00".to_string()),
+        ["@yearly" | "@annually"] => Some("/SC MONTHLY /M JAN /D 1 /ST 00:00".to_string()),
+        [minute, "*", "*", "*", "*"] if is_number(minute, 59) => {
+            Some(format!("/SC HOURLY /ST {}", time("0", minute)))
+        }
+        [minute, hour, "*", "*", "*"] if is_number(minute, 59) && is_number(hour, 23) => {
+            Some(format!("/SC DAILY /ST {}", time(hour, minute)))
+        }
+        [minute, hour, "*", "*", day] if is_number(minute, 59) && is_number(hour, 23) && is_number(day, 7) => {
+            const DAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+            let day: usize = day.parse().unwrap_or(0);
+            Some(format!("/SC WEEKLY /D {} /ST {}", DAYS[day], time(hour, minute)))
+        }
+        [minute, hour, day, "*", "*"]
+            if is_number(minute, 59) && is_number(hour, 23) && day.parse::<u32>().is_ok_and(|day| (1..=31).contains(&day)) =>
+        {
+            Some(format!("/SC MONTHLY /D {} /ST {}", day, time(hour, minute)))
+        }
+        [every, "*", "*", "*", "*"] => every
+            .strip_prefix("*/")
+            .filter(|interval| interval.parse::<u32>().is_ok_and(|interval| (1..=59).contains(&interval)))
+            .map(|interval| format!("/SC MINUTE /MO {}", interval)),
+        _ => None,
+    };
+
+    let schedule: String = schedule.ok_or_else(|| {
+        anyhow!(
+            "'{}' has no equivalent in the Task Scheduler. Use a shorthand like @daily, or a daily, weekly or monthly time such as \"30 2 * * 1\"",
+            expression
+        )
+    })?;
+
+    Ok(schedule.split(' ').map(str::to_string).collect())
+}
+
+/// Quote an argument the way Windows programs split their command line
+fn quote_windows_argument(argument: &str) -> String {
+    if !argument.is_empty() && !argument.contains([' ', '\t', '"']) {
+        return argument.to_string();
+    }
+
+    let mut quoted: String = String::from("\"");
+    let mut backslashes: usize = 0;
+    for c in argument.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are escaped along with it
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_CRONTAB: &str = "MAILTO=me@example.com\n# a comment\n0 1 * * * /usr/bin/true\n";
+
+    fn entry(expression: &str, program_name: &str, args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        render_schedule_entry(
+            expression,
+            Path::new("/usr/local/bin/spm"),
+            Path::new("/home/user/.spm"),
+            program_name,
+            &args,
+        )
+    }
+
+    #[test]
+    fn adding_an_entry_keeps_the_other_lines() {
+        let backup: String = entry("0 3 * * *", "backup", &[]);
+        let crontab: String = add_schedule_entry(FIXTURE_CRONTAB, "backup", &backup);
+        assert_eq!(crontab, format!("{}{}\n", FIXTURE_CRONTAB, backup));
+
+        // Scheduling again replaces the previous line of the program
+        let daily: String = entry("@daily", "backup", &[]);
+        let crontab: String = add_schedule_entry(&crontab, "backup", &daily);
+        assert_eq!(crontab, format!("{}{}\n", FIXTURE_CRONTAB, daily));
+
+        assert_eq!(add_schedule_entry("", "backup", &daily), format!("{}\n", daily));
+    }
+
+    #[test]
+    fn removing_entries_only_removes_the_lines_of_the_program() {
+        let crontab: String = format!(
+            "{}{}\n{}\n",
+            FIXTURE_CRONTAB,
+            entry("@daily", "backup", &[]),
+            entry("@hourly", "backup-extra", &[])
+        );
+
+        let (remaining, removed) = remove_schedule_entries(&crontab, "backup");
+        assert_eq!(removed, 1);
+        assert_eq!(
+            remaining,
+            format!("{}{}\n", FIXTURE_CRONTAB, entry("@hourly", "backup-extra", &[]))
+        );
+
+        assert_eq!(remove_schedule_entries(FIXTURE_CRONTAB, "backup"), (FIXTURE_CRONTAB.to_string(), 0));
+        assert_eq!(remove_schedule_entries(&entry("@daily", "backup", &[]), "backup"), (String::new(), 1));
+    }
+
+    #[test]
+    fn listing_entries_ignores_the_other_lines() {
+        let daily: String = entry("@daily", "backup", &[]);
+        let hourly: String = entry("@hourly", "report", &[]);
+        let crontab: String = format!("{}{}\n{}\n", FIXTURE_CRONTAB, daily, hourly);
+
+        assert_eq!(
+            list_schedule_entries(&crontab),
+            vec![("backup".to_string(), daily), ("report".to_string(), hourly)]
+        );
+        assert!(list_schedule_entries(FIXTURE_CRONTAB).is_empty());
+    }
+
+    #[test]
+    fn percent_signs_are_escaped_for_cron() {
+        let line: String = entry("@daily", "backup", &["--date=%Y-%m-%d"]);
+        assert!(line.contains("'--date=\\%Y-\\%m-\\%d'"));
+        assert!(!line.replace("\\%", "").contains('%'));
+    }
+
+    #[test]
+    fn task_scheduler_commands_follow_the_cron_expression() {
+        let render = |expression: &str, args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            render_task_scheduler_command(expression, Path::new(r"C:\Program Files\spm.exe"), "backup", &args)
+        };
+
+        assert_eq!(
+            render("30 2 * * *", &["--target", "two words"]).unwrap(),
+            r#"schtasks /Create /TN spm-backup /TR "\"C:\Program Files\spm.exe\" run --quiet --no-fancy backup --target \"two words\"" /SC DAILY /ST 02:30"#
+        );
+        assert!(render("0 9 * * 1", &[]).unwrap().ends_with("/SC WEEKLY /D MON /ST 09:00"));
+        assert!(render("15 4 1 * *", &[]).unwrap().ends_with("/SC MONTHLY /D 1 /ST 04:15"));
+        assert!(render("*/10 * * * *", &[]).unwrap().ends_with("/SC MINUTE /MO 10"));
+        assert!(render("@reboot", &[]).unwrap().ends_with("/SC ONSTART"));
+
+        assert!(render("0 3 * 6 *", &[]).is_err());
+        assert!(render("0 3,15 * * *", &[]).is_err());
+    }
+
+    #[test]
+    fn windows_arguments_are_quoted_when_needed() {
+        assert_eq!(quote_windows_argument("plain"), "plain");
+        assert_eq!(quote_windows_argument(""), "\"\"");
+        assert_eq!(quote_windows_argument("two words"), "\"two words\"");
+        assert_eq!(quote_windows_argument(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_windows_argument(r"C:\dir with space\"), r#""C:\dir with space\\""#);
+    }
+}
@@ -1,8 +1,18 @@
-use std::{fmt::Display, process::Command};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use anyhow::{Error, Result, anyhow};
 use serde::{Deserialize, Serialize};
 
+use crate::display_control::{Level, display_message};
+use crate::errors::ErrorCode;
+use crate::interrupt::while_child_runs;
+
 /// Represent various kind of shells
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 #[allow(clippy::doc_markdown)]
@@ -27,6 +37,35 @@ impl ShellType {
             ShellType::Zsh => "#!/usr/bin/env zsh",
         }
     }
+
+    /// Find the binary of the interpreter on the PATH.
+    /// `cmd` is always available on Windows and never elsewhere.
+    pub fn resolve_binary(&self) -> Option<PathBuf> {
+        match self {
+            ShellType::Cmd if cfg!(target_os = "windows") => Some(PathBuf::from("cmd")),
+            ShellType::Cmd => None,
+            _ => which::which(self.to_string()).ok(),
+        }
+    }
+}
+
+static IS_INTERPRETER_CHECKED: AtomicBool = AtomicBool::new(true);
+
+/// Allow scripts whose interpreter is missing, e.g. for `--ignore-interpreter`
+pub fn set_interpreter_check_enabled(is_enabled: bool) {
+    IS_INTERPRETER_CHECKED.store(is_enabled, Ordering::Relaxed);
+}
+
+/// Refuse a script whose interpreter cannot be found, unless the check is turned off
+pub fn ensure_interpreter_available(interpreter: &ShellType) -> Result<(), Error> {
+    if !IS_INTERPRETER_CHECKED.load(Ordering::Relaxed) || interpreter.resolve_binary().is_some() {
+        return Ok(());
+    }
+
+    Err(ErrorCode::InterpreterNotFound.error(format!(
+        "{} is not available on this system. Install it, or pass --ignore-interpreter to use sh instead",
+        interpreter
+    )))
 }
 
 impl From<String> for ShellType {
@@ -83,33 +122,38 @@ pub enum ExecutionContext {
 
 /// Execute a shell script with the specified execution context
 pub fn execute_shell_script_with_context(
-    shell_script: &str,
+    script_path: &Path,
+    interpreter: &ShellType,
     args: &[String],
+    envs: &[(String, String)],
     context: ExecutionContext,
 ) -> Result<(), Error> {
-    let script_path: &std::path::Path = std::path::Path::new(shell_script);
-
     // Determine the working directory based on the execution context
     let working_dir = match context {
         ExecutionContext::ScriptDirectory => script_path
             .parent()
-            .unwrap_or_else(|| std::path::Path::new(".")),
-        ExecutionContext::CurrentWorkingDirectory => std::path::Path::new("."),
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new(".")),
+        ExecutionContext::CurrentWorkingDirectory => Path::new("."),
     };
 
     if cfg!(target_os = "windows") {
+        display_message(Level::Debug, &format!("Executing {} with cmd", script_path.display()));
         let mut cmd = Command::new("cmd");
-        cmd.args(["/C", shell_script]).current_dir(working_dir);
-        // Add additional arguments if provided
-        if !args.is_empty() {
-            cmd.args(args);
+        cmd.current_dir(working_dir).envs(envs.iter().cloned());
+
+        // `cmd /C` parses its own command line, so the quoting that the standard
+        // library applies to each argument is not enough for paths with spaces.
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            cmd.raw_arg("/C")
+                .raw_arg(build_cmd_command_line(script_path.as_os_str(), args));
         }
 
-        match cmd.status() {
+        match while_child_runs(|| cmd.status()) {
             Ok(status) if !status.success() => {
-                return Err(anyhow!(
-                    "Windows CMD interpreter exited with a non-zero status"
-                ));
+                return Err(ScriptExitError::from_status(status).into());
             }
             Ok(_) => {}
             Err(e) => {
@@ -120,16 +164,24 @@ pub fn execute_shell_script_with_context(
         return Ok(());
     }
 
-    let mut cmd = Command::new("sh");
-    cmd.arg(shell_script).current_dir(working_dir);
+    ensure_interpreter_available(interpreter)?;
+    let binary: String = resolve_interpreter(interpreter);
+    display_message(
+        Level::Debug,
+        &format!("Executing {} with {} in {}", script_path.display(), binary, working_dir.display()),
+    );
+    let mut cmd = Command::new(binary);
+    cmd.arg(script_path)
+        .current_dir(working_dir)
+        .envs(envs.iter().cloned());
     // Add additional arguments if provided
     if !args.is_empty() {
         cmd.args(args);
     }
 
-    match cmd.status() {
+    match while_child_runs(|| cmd.status()) {
         Ok(status) if !status.success() => {
-            return Err(anyhow!("Shell interpreter exited with a non-zero status"));
+            return Err(ScriptExitError::from_status(status).into());
         }
         Ok(_) => {}
         Err(e) => {
@@ -139,3 +191,183 @@ pub fn execute_shell_script_with_context(
 
     Ok(())
 }
+
+/// Start an interactive shell in the current working directory, with the given environment
+pub fn execute_interactive_shell(interpreter: &ShellType, envs: &[(String, String)]) -> Result<(), Error> {
+    let binary: String = if cfg!(target_os = "windows") {
+        ShellType::Cmd.to_string()
+    } else {
+        ensure_interpreter_available(interpreter)?;
+        resolve_interpreter(interpreter)
+    };
+
+    // Ctrl-C at the prompt is for the shell, which only ends with `exit`
+    let status = while_child_runs(|| Command::new(binary).envs(envs.iter().cloned()).status())
+        .map_err(|e| anyhow!("Failed to start shell interpreter: {}", e))?;
+
+    if !status.success() {
+        return Err(ScriptExitError::from_status(status).into());
+    }
+
+    Ok(())
+}
+
+/// A script that ran but did not succeed, carrying the exit code that spm
+/// should exit with itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptExitError {
+    pub code: i32,
+}
+
+impl ScriptExitError {
+    /// Convert an exit status, mapping a terminating signal to 128 + signal
+    /// like shells do
+    pub fn from_status(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Self { code: 128 + signal };
+            }
+        }
+
+        Self { code: status.code().unwrap_or(1) }
+    }
+}
+
+impl Display for ScriptExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The script exited with status {} (run `spm explain {}` for more)",
+            self.code,
+            ErrorCode::ScriptFailed.get_code()
+        )
+    }
+}
+
+impl std::error::Error for ScriptExitError {}
+
+/// Returns the binary to run a script with, falling back to `sh` with a
+/// warning when the declared interpreter is not on the PATH.
+fn resolve_interpreter(interpreter: &ShellType) -> String {
+    let name: String = interpreter.to_string();
+
+    if interpreter.resolve_binary().is_some() {
+        return name;
+    }
+
+    if *interpreter != ShellType::Sh {
+        display_message(
+            Level::Warn,
+            &format!("{} is not available, running the script with sh instead", name),
+        );
+    }
+
+    ShellType::Sh.to_string()
+}
+
+/// Check the syntax of a script with its interpreter's no-exec mode (`-n`).
+///
+/// Returns the interpreter's error text when the script does not parse.
+pub fn check_shell_script_syntax(
+    script_path: &Path,
+    interpreter: &ShellType,
+) -> Result<Option<String>, Error> {
+    if *interpreter == ShellType::Cmd {
+        return Err(anyhow!("cmd scripts do not support syntax checking"));
+    }
+
+    let output = Command::new(interpreter.to_string())
+        .arg("-n")
+        .arg(script_path)
+        .output()
+        .map_err(|error| anyhow!("Failed to start {} interpreter: {}", interpreter, error))?;
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+}
+
+/// Quote a single argument for the `cmd` command line.
+///
+/// Quotes keep `^` and the other special characters literal, but not `%`,
+/// which `cmd` expands anywhere. Each `%` is placed outside of the quotes,
+/// escaped with a `^`, so that `%PATH%` reaches the script as it was written.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn quote_cmd_argument(argument: &OsStr) -> OsString {
+    let text = argument.to_string_lossy();
+    let needs_quotes: bool = text.is_empty()
+        || text
+            .chars()
+            .any(|c| c.is_whitespace() || "&|<>^()%!,;=\"".contains(c));
+
+    if !needs_quotes {
+        return argument.to_os_string();
+    }
+
+    let mut quoted = OsString::from("\"");
+    quoted.push(text.replace('"', "\"\"").replace('%', "\"^%\""));
+    quoted.push("\"");
+    quoted
+}
+
+/// Build the command line passed to `cmd /C` to run a script with arguments.
+///
+/// The whole line is wrapped in an extra pair of quotes, because `cmd` strips
+/// the first and the last quote when the line contains more than two of them.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn build_cmd_command_line(script_path: &OsStr, args: &[String]) -> OsString {
+    let mut command_line = OsString::from("\"");
+    command_line.push(quote_cmd_argument(script_path));
+
+    for arg in args {
+        command_line.push(" ");
+        command_line.push(quote_cmd_argument(OsStr::new(arg)));
+    }
+
+    command_line.push("\"");
+    command_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(argument: &str) -> String {
+        quote_cmd_argument(OsStr::new(argument)).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn plain_arguments_are_left_alone() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote(r"C:\tools\run.sh"), r"C:\tools\run.sh");
+    }
+
+    #[test]
+    fn special_characters_are_quoted() {
+        assert_eq!(quote(""), r#""""#);
+        assert_eq!(quote("two words"), r#""two words""#);
+        assert_eq!(quote("a&b|c"), r#""a&b|c""#);
+        assert_eq!(quote("caret^"), r#""caret^""#);
+        assert_eq!(quote(r#"say "hi""#), r#""say ""hi""""#);
+    }
+
+    #[test]
+    fn percent_signs_are_escaped_outside_of_the_quotes() {
+        assert_eq!(quote("%PATH%"), r#"""^%"PATH"^%"""#);
+        assert_eq!(quote("100% done"), r#""100"^%" done""#);
+    }
+
+    #[test]
+    fn command_lines_are_wrapped_in_an_extra_pair_of_quotes() {
+        let args: Vec<String> = vec!["first".to_string(), "two words".to_string(), "%HOME%".to_string()];
+        let command_line: OsString = build_cmd_command_line(OsStr::new(r"C:\My Scripts\run.cmd"), &args);
+        assert_eq!(
+            command_line.to_string_lossy(),
+            r#"""C:\My Scripts\run.cmd" first "two words" ""^%"HOME"^%""""#
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Error, Result, anyhow};
+
+use crate::errors::ErrorCode;
+use crate::program::{ProgramMetadata, SourceKind, collect_scripts_from_directory};
+use crate::progress::{ProgressEvent, report_progress};
+use crate::utilities::{
+    CloneDepth, checkout_git_reference, cleanup_temp_repository, compute_sha256,
+    create_temp_directory, download_file, get_program_name_from_url, is_git_shorthand, is_git_url,
+    is_offline, is_single_file_url, looks_like_shell_script, split_git_reference, split_git_subdirectory,
+    update_git_cache,
+};
+
+/// Settings that change how a source is fetched
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceOptions<'a> {
+    /// The tag, branch or commit to check out, overriding a `<url>@<ref>` suffix
+    pub git_reference: Option<&'a str>,
+    /// The name to give a downloaded script instead of the one in the URL
+    pub program_name: Option<&'a str>,
+    /// The SHA-256 digest a downloaded script must match
+    pub expected_sha256: Option<&'a str>,
+    /// Treat a URL as a single script even when it does not end with `.sh`
+    pub is_single_file: bool,
+    /// Clone the whole history of a git repository instead of the last commit
+    pub is_full_clone: bool,
+    /// Only install the scripts of this directory of a git repository,
+    /// overriding a `<url>//<subdirectory>` suffix
+    pub subdirectory: Option<&'a str>,
+    /// Where `owner/repository` shorthands are hosted, e.g. `https://github.com`
+    pub base_url: Option<&'a str>,
+}
+
+impl<'a> SourceOptions<'a> {
+    /// The options that fetch a recorded source again the way it was installed
+    pub fn for_recorded_source(
+        kind: Option<SourceKind>,
+        git_reference: Option<&'a str>,
+        program_name: Option<&'a str>,
+    ) -> Self {
+        Self {
+            git_reference,
+            program_name,
+            // A download is fetched as a single script even when its url does not look like one
+            is_single_file: kind == Some(SourceKind::Url),
+            ..Default::default()
+        }
+    }
+}
+
+/// A source fetched and ready to be installed.
+/// Files staged in spm's temporary directory are removed when it is dropped.
+#[derive(Debug)]
+pub struct ResolvedSource {
+    /// Where the fetched files are
+    pub staging_path: PathBuf,
+    /// The scripts found in the source
+    pub scripts: Vec<PathBuf>,
+    /// How to refer to the source in messages
+    pub display_name: String,
+    /// Provenance recorded with every program installed from the source
+    pub metadata: ProgramMetadata,
+    /// Whether the scripts are all the programs the source provides, so that
+    /// installed programs missing from them were removed upstream
+    pub is_every_program: bool,
+    is_staged: bool,
+}
+
+impl Drop for ResolvedSource {
+    fn drop(&mut self) {
+        if self.is_staged {
+            let _ = cleanup_temp_repository(&self.staging_path);
+        }
+    }
+}
+
+/// Fetches one kind of source
+pub trait SourceResolver {
+    /// Whether the source is of the kind this resolver fetches
+    fn matches(&self, source: &str, options: &SourceOptions) -> bool;
+
+    /// Fetch the source and find the scripts in it
+    fn resolve(&self, source: &str, options: &SourceOptions) -> Result<ResolvedSource, Error>;
+}
+
+/// Resolvers in the order they are tried, the last one matching any source
+const SOURCE_RESOLVERS: [&dyn SourceResolver; 3] =
+    [&RawUrlResolver, &GitRepositoryResolver, &LocalPathResolver];
+
+/// Pick the resolver of a source
+pub fn select_source_resolver(source: &str, options: &SourceOptions) -> &'static dyn SourceResolver {
+    SOURCE_RESOLVERS
+        .into_iter()
+        .find(|resolver| resolver.matches(source, options))
+        .unwrap_or(&LocalPathResolver)
+}
+
+/// A script on the local file system, installed from where it is
+pub struct LocalPathResolver;
+
+impl SourceResolver for LocalPathResolver {
+    fn matches(&self, _source: &str, _options: &SourceOptions) -> bool {
+        true
+    }
+
+    fn resolve(&self, source: &str, _options: &SourceOptions) -> Result<ResolvedSource, Error> {
+        let path: &Path = Path::new(source);
+        if !path.exists() {
+            return Err(anyhow!("Path not found: {}", source));
+        }
+
+        Ok(ResolvedSource {
+            staging_path: path.to_path_buf(),
+            scripts: vec![path.to_path_buf()],
+            display_name: path.display().to_string(),
+            // Remember the original file so that the program can be updated from it
+            metadata: ProgramMetadata {
+                source: path.canonicalize().ok().map(|path| path.display().to_string()),
+                kind: Some(SourceKind::Local),
+                ..Default::default()
+            },
+            is_every_program: false,
+            is_staged: false,
+        })
+    }
+}
+
+/// A git repository, fetched into the cache through the configured mirrors
+pub struct GitRepositoryResolver;
+
+impl SourceResolver for GitRepositoryResolver {
+    fn matches(&self, source: &str, options: &SourceOptions) -> bool {
+        is_git_url(source)
+            || (options.base_url.is_some() && is_git_shorthand(source) && !Path::new(source).exists())
+    }
+
+    fn resolve(&self, source: &str, options: &SourceOptions) -> Result<ResolvedSource, Error> {
+        // Expand the `owner/repository` shorthand into a URL on the base url
+        let source: String = match options.base_url {
+            Some(base_url) if !is_git_url(source) && is_git_shorthand(source) => {
+                format!("{}/{}", base_url.trim_end_matches('/'), source.trim())
+            }
+            _ => source.trim().to_string(),
+        };
+
+        let (git_url, git_reference) = split_git_reference(&source);
+        let git_reference: Option<&str> = options.git_reference.or(git_reference.as_deref());
+        let (git_url, subdirectory) = split_git_subdirectory(&git_url);
+        let subdirectory: Option<&str> = options
+            .subdirectory
+            .map(|subdirectory| subdirectory.trim_matches('/'))
+            .or(subdirectory.as_deref());
+
+        // A subdirectory is part of the source, so that updates install from it again
+        let source: String = match subdirectory {
+            Some(subdirectory) => format!("{}//{}", git_url, subdirectory),
+            None => git_url.clone(),
+        };
+
+        // Only the requested commit is fetched, unless the whole history is asked for
+        let depth: CloneDepth = match git_reference {
+            _ if options.is_full_clone => CloneDepth::Full,
+            Some(git_reference) => CloneDepth::Reference(git_reference),
+            None => CloneDepth::Latest,
+        };
+
+        // Discard any leftovers of an interrupted install before checking out
+        let repo_path: PathBuf = create_temp_directory()?.join("repo");
+        cleanup_temp_repository(&repo_path)?;
+        let (cache_path, served_by) = update_git_cache(&git_url, depth)?;
+
+        let mut resolved = ResolvedSource {
+            staging_path: repo_path,
+            scripts: Vec::new(),
+            display_name: source.clone(),
+            metadata: ProgramMetadata {
+                source: Some(source),
+                kind: Some(SourceKind::Git),
+                mirror: (served_by != git_url).then_some(served_by),
+                version: git_reference.map(str::to_string),
+                ..Default::default()
+            },
+            is_every_program: true,
+            is_staged: true,
+        };
+
+        // A commit is neither a tag nor a branch, so it needs the whole history
+        let commit: String = match checkout_git_reference(&cache_path, git_reference, &resolved.staging_path) {
+            Err(_) if depth != CloneDepth::Full && !is_offline() => {
+                update_git_cache(&git_url, CloneDepth::Full)?;
+                checkout_git_reference(&cache_path, git_reference, &resolved.staging_path)?
+            }
+            result => result?,
+        };
+        resolved.metadata.commit = Some(commit);
+
+        let scripts_path: PathBuf = match subdirectory {
+            Some(subdirectory) => get_repository_subdirectory(&resolved.staging_path, subdirectory)?,
+            None => resolved.staging_path.clone(),
+        };
+        collect_scripts_from_directory(&scripts_path, &mut resolved.scripts)?;
+
+        Ok(resolved)
+    }
+}
+
+/// Find a directory of a cloned repository, refusing paths that leave it
+fn get_repository_subdirectory(repository_path: &Path, subdirectory: &str) -> Result<PathBuf, Error> {
+    let relative_path: &Path = Path::new(subdirectory);
+    let is_inside: bool = relative_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    let path: PathBuf = repository_path.join(relative_path);
+
+    if !is_inside || !path.is_dir() {
+        return Err(anyhow!("The repository has no directory named {}", subdirectory));
+    }
+
+    Ok(path)
+}
+
+/// The name a script downloaded from a URL is installed under.
+/// Names that would place the script outside the programs directory are refused.
+pub fn get_download_name(url: &str, program_name: Option<&str>) -> Result<String, Error> {
+    let name: String = match program_name {
+        Some(name) => name.strip_suffix(".sh").unwrap_or(name).to_string(),
+        None => get_program_name_from_url(url)
+            .ok_or_else(|| anyhow!("Cannot derive a program name from {}. Use `--as <name>`", url))?,
+    };
+
+    let is_plain_file_name: bool = matches!(
+        Path::new(&name).components().collect::<Vec<Component>>().as_slice(),
+        [Component::Normal(_)]
+    ) && !name.contains(['/', '\\', ':']);
+    if !is_plain_file_name {
+        return Err(anyhow!(
+            "'{}' cannot be used as a program name, it must be a file name without path separators or `..`",
+            name
+        ));
+    }
+
+    Ok(name)
+}
+
+/// A single script downloaded from a URL, checked before it is installed
+pub struct RawUrlResolver;
+
+impl SourceResolver for RawUrlResolver {
+    fn matches(&self, source: &str, options: &SourceOptions) -> bool {
+        options.is_single_file || is_single_file_url(source)
+    }
+
+    fn resolve(&self, source: &str, options: &SourceOptions) -> Result<ResolvedSource, Error> {
+        let program_name: String = get_download_name(source, options.program_name)?;
+
+        let download_dir: PathBuf = create_temp_directory()?.join("download");
+        cleanup_temp_repository(&download_dir)?;
+        std::fs::create_dir_all(&download_dir)?;
+        let script_path: PathBuf = download_dir.join(format!("{}.sh", program_name));
+
+        let resolved = ResolvedSource {
+            staging_path: download_dir,
+            scripts: vec![script_path.clone()],
+            display_name: source.to_string(),
+            metadata: ProgramMetadata {
+                source: Some(source.to_string()),
+                kind: Some(SourceKind::Url),
+                ..Default::default()
+            },
+            is_every_program: false,
+            is_staged: true,
+        };
+
+        report_progress(ProgressEvent::new("download", source).with_program(&program_name));
+        download_file(source, &script_path)?;
+
+        if !looks_like_shell_script(&std::fs::read(&script_path)?) {
+            return Err(ErrorCode::UnsupportedScript
+                .error(format!("The content at {} does not look like a shell script", source)));
+        }
+
+        if let Some(expected) = options.expected_sha256 {
+            let actual: String = compute_sha256(&script_path)?;
+            if !actual.eq_ignore_ascii_case(expected.trim()) {
+                return Err(ErrorCode::ChecksumMismatch.error(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    source,
+                    expected.trim(),
+                    actual
+                )));
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+    use crate::utilities::set_spm_root_directory;
+
+    /// Stage fetched files under a root of their own instead of `~/.spm`
+    fn use_temporary_root() {
+        static ROOT: OnceLock<tempfile::TempDir> = OnceLock::new();
+        let root: &tempfile::TempDir = ROOT.get_or_init(|| tempfile::tempdir().unwrap());
+        set_spm_root_directory(root.path()).unwrap();
+    }
+
+    fn write_script(directory: &Path, name: &str, body: &str) -> PathBuf {
+        let path: PathBuf = directory.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        path
+    }
+
+    #[test]
+    fn recorded_downloads_are_fetched_as_single_scripts() {
+        use_temporary_root();
+        let directory = tempfile::tempdir().unwrap();
+        let script: PathBuf = write_script(directory.path(), "raw", "echo raw");
+        let url: String = format!("file://{}", script.display());
+
+        // Without the recorded kind, the url would be cloned as a repository
+        assert!(!RawUrlResolver.matches(&url, &SourceOptions::default()));
+
+        let options = SourceOptions::for_recorded_source(Some(SourceKind::Url), None, Some("tool"));
+        let resolved: ResolvedSource = select_source_resolver(&url, &options)
+            .resolve(&url, &options)
+            .unwrap();
+        assert_eq!(resolved.scripts.len(), 1);
+        assert!(resolved.scripts[0].ends_with("tool.sh"));
+        assert_eq!(resolved.metadata.kind, Some(SourceKind::Url));
+
+        let options = SourceOptions::for_recorded_source(Some(SourceKind::Git), Some("main"), None);
+        assert!(!options.is_single_file);
+        assert_eq!(options.git_reference, Some("main"));
+    }
+
+    #[test]
+    fn resolvers_match_their_kind_of_source() {
+        let options = SourceOptions::default();
+        assert!(RawUrlResolver.matches("https://example.com/tool.sh", &options));
+        assert!(!RawUrlResolver.matches("https://example.com/repository.git", &options));
+        assert!(RawUrlResolver.matches(
+            "https://example.com/install",
+            &SourceOptions {
+                is_single_file: true,
+                ..Default::default()
+            }
+        ));
+
+        assert!(GitRepositoryResolver.matches("https://example.com/repository.git", &options));
+        assert!(!GitRepositoryResolver.matches("owner/repository", &options));
+        assert!(GitRepositoryResolver.matches(
+            "owner/repository",
+            &SourceOptions {
+                base_url: Some("https://github.com"),
+                ..Default::default()
+            }
+        ));
+
+        assert!(LocalPathResolver.matches("tool.sh", &options));
+    }
+
+    #[test]
+    fn download_names_stay_in_the_programs_directory() {
+        let url: &str = "https://example.com/tool.sh";
+        assert_eq!(get_download_name(url, None).unwrap(), "tool");
+        assert_eq!(get_download_name(url, Some("other.sh")).unwrap(), "other");
+
+        for name in ["../escape", "..", "/tmp/escape", "nested/name", "nested\\name", "", "C:name"] {
+            assert!(get_download_name(url, Some(name)).is_err(), "{}", name);
+        }
+        assert!(get_download_name("https://example.com/..", None).is_err());
+    }
+
+    #[test]
+    fn local_path_resolver_uses_the_script_in_place() {
+        let directory = tempfile::tempdir().unwrap();
+        let script: PathBuf = write_script(directory.path(), "tool.sh", "echo tool");
+
+        let resolved: ResolvedSource = LocalPathResolver
+            .resolve(script.to_str().unwrap(), &SourceOptions::default())
+            .unwrap();
+        assert_eq!(resolved.scripts, vec![script.clone()]);
+        assert_eq!(
+            resolved.metadata.source,
+            Some(script.canonicalize().unwrap().display().to_string())
+        );
+        assert_eq!(resolved.metadata.kind, Some(SourceKind::Local));
+        assert!(!resolved.is_every_program);
+
+        // The original script is not removed with the resolved source
+        drop(resolved);
+        assert!(script.exists());
+
+        let missing = LocalPathResolver.resolve(
+            directory.path().join("missing.sh").to_str().unwrap(),
+            &SourceOptions::default(),
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn raw_url_resolver_stages_the_download_under_its_name() {
+        use_temporary_root();
+        let directory = tempfile::tempdir().unwrap();
+        let script: PathBuf = write_script(directory.path(), "remote.sh", "echo remote");
+        let url: String = format!("file://{}", script.display());
+
+        let resolved: ResolvedSource = RawUrlResolver
+            .resolve(
+                &url,
+                &SourceOptions {
+                    program_name: Some("tool"),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(resolved.scripts.len(), 1);
+        assert!(resolved.scripts[0].ends_with("tool.sh"));
+        assert!(std::fs::read_to_string(&resolved.scripts[0]).unwrap().contains("echo remote"));
+        assert_eq!(resolved.metadata.source, Some(url.clone()));
+        assert_eq!(resolved.metadata.kind, Some(SourceKind::Url));
+        assert!(!resolved.is_every_program);
+
+        let staging_path: PathBuf = resolved.staging_path.clone();
+        drop(resolved);
+        assert!(!staging_path.exists());
+
+        let mismatch = RawUrlResolver.resolve(
+            &url,
+            &SourceOptions {
+                expected_sha256: Some("0000"),
+                ..Default::default()
+            },
+        );
+        assert!(mismatch.is_err());
+    }
+
+    #[test]
+    fn git_repository_resolver_checks_out_every_script() {
+        use_temporary_root();
+        let directory = tempfile::tempdir().unwrap();
+        let repository = git2::Repository::init(directory.path()).unwrap();
+        write_script(directory.path(), "one.sh", "echo one");
+        write_script(directory.path(), "two.sh", "echo two");
+
+        let mut index = repository.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("spm", "spm@example.com").unwrap();
+        let commit = repository
+            .commit(Some("HEAD"), &signature, &signature, "scripts", &tree, &[])
+            .unwrap();
+
+        let url: String = format!("file://{}", directory.path().display());
+        let resolved: ResolvedSource = GitRepositoryResolver
+            .resolve(&url, &SourceOptions::default())
+            .unwrap();
+        let mut names: Vec<String> = resolved
+            .scripts
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["one.sh", "two.sh"]);
+        assert_eq!(resolved.metadata.source, Some(url));
+        assert_eq!(resolved.metadata.commit, Some(commit.to_string()));
+        assert_eq!(resolved.metadata.kind, Some(SourceKind::Git));
+        assert!(resolved.is_every_program);
+
+        let staging_path: PathBuf = resolved.staging_path.clone();
+        drop(resolved);
+        assert!(!staging_path.exists());
+    }
+}
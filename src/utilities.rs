@@ -1,24 +1,97 @@
 use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{IsTerminal, Read},
     path::{Path, PathBuf},
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{Error, Result, anyhow};
 use auth_git2::GitAuthenticator;
-use git2::{Config, FetchOptions, ProxyOptions, RemoteCallbacks, build::RepoBuilder};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use git2::{
+    AutotagOption, Commit, Config, ErrorClass, ErrorCode as GitErrorCode, FetchOptions,
+    ProxyOptions, RemoteCallbacks, Repository, build::CheckoutBuilder,
+};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    display_control::{display_form, display_message, display_tree_message, input_message, Level},
-    program::{ProgramManager, Program},
-    properties::{DEFAULT_SPM_FOLDER, DEFAULT_TEMPORARY_FOLDER},
-    shell::{execute_shell_script_with_context, ExecutionContext},
+    arguments::{Arguments, NewArguments},
+    configuration::{Configuration, Registry},
+    display_control::{
+        confirm, display_counts, display_form, display_message, display_note, display_progress,
+        display_tree_message, display_tree_output, finish_progress, format_byte_size, input_message, pick_from_list,
+        quote_path, Level,
+    },
+    errors::{ErrorCode, ErrorCollection},
+    program::{InstallSummary, Program, ProgramInfo, ProgramManager, ProgramMetadata},
+    progress::{ProgressEvent, report_progress},
+    registry::RegistryMatch,
+    properties::{
+        DEFAULT_CACHE_FOLDER, DEFAULT_SPM_FOLDER, DEFAULT_TEMPORARY_FOLDER,
+        SPM_HOME_ENVIRONMENT_VARIABLE,
+    },
+    schedule::{
+        add_schedule_entry, list_schedule_entries, read_crontab, remove_schedule_entries,
+        render_schedule_entry, render_task_scheduler_command, validate_cron_expression,
+        write_crontab,
+    },
+    shell::{
+        execute_interactive_shell, execute_shell_script_with_context, ExecutionContext, ShellType,
+    },
 };
 
-// Create the temporary directory for cloning remote repositories
-pub fn create_temp_directory() -> Result<PathBuf, Error> {
-    let temp_dir = dirs::home_dir()
+/// The root directory given with `--home`, which takes precedence over `SPM_HOME`
+static ROOT_DIRECTORY_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use another root directory for the rest of the invocation
+pub fn set_spm_root_directory(root_directory: &Path) -> Result<(), Error> {
+    // Scripts and crontab lines receive the root, so it must not depend on the working directory
+    let _ = ROOT_DIRECTORY_OVERRIDE.set(std::path::absolute(root_directory)?);
+
+    Ok(())
+}
+
+/// Returns the root directory of spm, which is `~/.spm` unless `--home` or
+/// the `SPM_HOME` environment variable points somewhere else.
+pub fn get_spm_root_directory() -> Result<PathBuf, Error> {
+    if let Some(root_directory) = ROOT_DIRECTORY_OVERRIDE.get() {
+        return Ok(root_directory.clone());
+    }
+
+    if let Some(root_directory) =
+        std::env::var_os(SPM_HOME_ENVIRONMENT_VARIABLE).filter(|root| !root.is_empty())
+    {
+        // Scripts and crontab lines receive the root, as with `--home`
+        return Ok(std::path::absolute(root_directory)?);
+    }
+
+    Ok(dirs::home_dir()
         .ok_or_else(|| anyhow!("Failed to locate home directory"))?
-        .join(DEFAULT_SPM_FOLDER)
-        .join("temp");
+        .join(DEFAULT_SPM_FOLDER))
+}
+
+/// The name of the temporary directory of this process, from its pid and a
+/// random suffix, so that concurrent invocations never share files
+static TEMP_DIRECTORY_NAME: OnceLock<String> = OnceLock::new();
+
+fn get_temp_directory_name() -> &'static str {
+    TEMP_DIRECTORY_NAME.get_or_init(|| {
+        let suffix: u64 = RandomState::new().build_hasher().finish();
+        format!("{}-{:08x}", std::process::id(), suffix as u32)
+    })
+}
+
+// Create the temporary directory of this process for cloning remote repositories
+pub fn create_temp_directory() -> Result<PathBuf, Error> {
+    let temp_dir = get_spm_root_directory()?
+        .join(DEFAULT_TEMPORARY_FOLDER)
+        .join(get_temp_directory_name());
 
     // Create the temp directory if it doesn't exist
     if !temp_dir.exists() {
@@ -28,15 +101,97 @@ pub fn create_temp_directory() -> Result<PathBuf, Error> {
     Ok(temp_dir)
 }
 
+/// Remove the temporary directory of this process, if it made one
+pub fn remove_temp_directory() -> Result<(), Error> {
+    let temp_dir: PathBuf = get_spm_root_directory()?
+        .join(DEFAULT_TEMPORARY_FOLDER)
+        .join(get_temp_directory_name());
+    if temp_dir.exists() {
+        std::fs::remove_dir_all(temp_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Whether a process with this pid is running. When that cannot be told, the
+/// process is assumed to be running, so that its files are kept.
+fn is_process_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+
+    let output = if cfg!(windows) {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+    } else {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .output()
+    };
+
+    match output {
+        // tasklist succeeds either way, and only lists the process when it exists
+        Ok(output) if cfg!(windows) => String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .any(|word| word == pid.to_string()),
+        Ok(output) => output.status.success(),
+        Err(_) => true,
+    }
+}
+
+/// Remove what spm processes left in the temporary folder, such as after a
+/// crash, once it is older than `max_age`. The directories of processes that
+/// are still running are kept. Returns how many entries were removed.
+pub fn sweep_temp_directories(max_age: Duration) -> Result<usize, Error> {
+    let temp_root: PathBuf = get_spm_root_directory()?.join(DEFAULT_TEMPORARY_FOLDER);
+    if !temp_root.exists() {
+        return Ok(0);
+    }
+
+    let mut removed: usize = 0;
+    for entry in std::fs::read_dir(&temp_root)? {
+        let entry = entry?;
+        if entry.file_name() == get_temp_directory_name() {
+            continue;
+        }
+
+        // Directories are named after the pid of the process that made them
+        let owner: Option<u32> = entry
+            .file_name()
+            .to_string_lossy()
+            .split('-')
+            .next()
+            .and_then(|pid| pid.parse().ok());
+        if owner.is_some_and(is_process_running) {
+            continue;
+        }
+
+        let age: Duration = entry
+            .metadata()?
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        if age < max_age {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
 // Clean up the temporary directory for a specific repository
 pub fn cleanup_temp_repository(repo_path: &Path) -> Result<(), Error> {
     if repo_path.exists()
-        && repo_path.starts_with(
-            dirs::home_dir()
-                .unwrap()
-                .join(DEFAULT_SPM_FOLDER)
-                .join(DEFAULT_TEMPORARY_FOLDER),
-        )
+        && repo_path.starts_with(get_spm_root_directory()?.join(DEFAULT_TEMPORARY_FOLDER))
     {
         std::fs::remove_dir_all(repo_path)?;
     }
@@ -44,74 +199,333 @@ pub fn cleanup_temp_repository(repo_path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+/// Run an installed program, placing its default arguments before the given ones
+fn execute_installed_program(
+    program: &Program,
+    args: &[String],
+    is_default_args_enabled: bool,
+) -> Result<(), Error> {
+    let path: &Path = program
+        .get_program_path()
+        .ok_or_else(|| anyhow!("Program path not available"))?;
+    display_message(
+        Level::Logging,
+        &format!("Running program: {}", program.get_name()),
+    );
+
+    let mut full_args: Vec<String> = Vec::new();
+    let default_args: Option<String> = ProgramMetadata::load_or_default(path)
+        .default_args
+        .filter(|_| is_default_args_enabled);
+    if let Some(default_args) = default_args {
+        full_args = shell_words::split(&default_args)
+            .map_err(|e| anyhow!("Invalid default arguments '{}': {}", default_args, e))?;
+        display_note(&format!("Applied default arguments: {}", default_args));
+    }
+    full_args.extend_from_slice(args);
+
+    // Execute from current working directory when using spm run
+    execute_shell_script_with_context(
+        path,
+        program.get_interpreter(),
+        &full_args,
+        &program.get_environment()?,
+        ExecutionContext::CurrentWorkingDirectory,
+    )
+}
+
 pub fn execute_run_command(
     program_manager: &ProgramManager,
     expression: String,
     args: &[String],
+    is_default_args_enabled: bool,
 ) -> Result<(), Error> {
     let path: &Path = Path::new(&expression);
 
+    // Case 0: the script is piped through stdin
+    if expression == "-" {
+        return execute_script_from_stdin(args);
+    }
+
+    let program: Program = find_program_to_run(program_manager, &expression)?;
+
     // Case 1: input is a shell script file
     if path.is_file() {
         // Execute regular shell script in the current working directory
         return execute_shell_script_with_context(
-            &expression,
+            path,
+            program.get_interpreter(),
             args,
+            &program.get_environment()?,
             ExecutionContext::CurrentWorkingDirectory,
         );
     }
 
-    // Case 2: Check if it's an installed program name
-    let program_candidates: Vec<Program> = program_manager.keyword_search(&expression)?;
+    // Case 2: input names an installed program
+    execute_installed_program(&program, args, is_default_args_enabled)
+}
+
+/// Start the interpreter of a program interactively, with the environment
+/// the program gets when spm runs it
+pub fn open_program_shell(program_manager: &ProgramManager, expression: &str) -> Result<(), Error> {
+    let program: Program = find_program_to_run(program_manager, expression)?;
+
+    display_message(
+        Level::Logging,
+        &format!(
+            "Starting {} with the environment of {}. Type `exit` or press Ctrl-D to leave.",
+            program.get_interpreter(),
+            program.get_name()
+        ),
+    );
+
+    execute_interactive_shell(program.get_interpreter(), &program.get_environment()?)
+}
+
+/// Find the program that a `spm run` expression refers to: the path of a
+/// script, or keywords of an installed program
+fn find_program_to_run(program_manager: &ProgramManager, expression: &str) -> Result<Program, Error> {
+    let path: &Path = Path::new(expression);
+
+    if path.is_file() {
+        return Program::from_file(path);
+    }
+
+    // An exact name wins over keywords, so that `backup` runs even when
+    // `backup-db` is installed too, as scheduled runs expect
+    if let Ok(program) = program_manager.get_program_by_name(expression.to_string()) {
+        return Ok(program);
+    }
 
-    if !program_candidates.is_empty() {
-        // Run the program if it is exactly one match
-        if program_candidates.len() == 1 {
-            let program = &program_candidates[0];
-            display_message(
-                Level::Logging,
-                &format!("Running program: {}", program.get_name()),
-            );
-            // Execute from current working directory when using spm run
-            return execute_shell_script_with_context(
-                program.get_program_path().ok_or_else(|| anyhow!("Program path not available"))?,
-                args,
-                ExecutionContext::CurrentWorkingDirectory,
-            );
+    pick_program(
+        program_manager.keyword_search(expression)?,
+        expression,
+        "Please select a program to execute:",
+    )
+}
+
+/// Uninstall every program installed from a source after listing them in a
+/// single confirmation. Carries on past failures and returns them along with
+/// the number of programs removed.
+pub fn uninstall_programs_from_source(
+    program_manager: &ProgramManager,
+    source: &str,
+    is_confirmed: bool,
+) -> Result<(usize, ErrorCollection), Error> {
+    let programs: Vec<Program> = program_manager.get_programs_from_source(source)?;
+    if programs.is_empty() {
+        return Err(anyhow!("No programs were installed from '{}'", source));
+    }
+
+    let names: Vec<&str> = programs.iter().map(|program| program.get_name()).collect();
+    if !is_confirmed && !confirm(&format!("Uninstall {}?", names.join(", ")))? {
+        return Ok((0, ErrorCollection::new()));
+    }
+
+    let mut failures = ErrorCollection::new();
+    let mut removed: usize = 0;
+    for program in &programs {
+        match program_manager.uninstall_program_by_name(program.get_name().to_string()) {
+            Ok(_) => {
+                display_tree_message(1, &format!("Removed: {}", program.get_name()));
+                removed += 1;
+            }
+            Err(error) => failures.push(program.get_name(), error),
         }
+    }
+
+    Ok((removed, failures))
+}
+
+/// Find the installed program to uninstall: the index shown by `spm list`,
+/// the name of a program, or keywords of one. Keywords are only resolved by
+/// asking, never when nobody can answer, even when they match a single program.
+fn find_program_to_uninstall(
+    program_manager: &ProgramManager,
+    expression: &str,
+    is_interactive: bool,
+) -> Result<Program, Error> {
+    if let Ok(program) = program_manager.get_program_by_name(expression.to_string()) {
+        return Ok(program);
+    }
+
+    if let Ok(index) = expression.parse::<usize>() {
+        let programs: Vec<Program> = program_manager.get_installed_programs()?;
+        let count: usize = programs.len();
+
+        return programs.into_iter().nth(index).ok_or_else(|| {
+            ErrorCode::ProgramNotFound.error(match count {
+                0 => format!("No program at index {}, none are installed", index),
+                count => format!(
+                    "No program at index {}. Run `spm list` to see the indexes, from 0 to {}",
+                    index,
+                    count - 1
+                ),
+            })
+        });
+    }
+
+    let candidates: Vec<Program> = program_manager.keyword_search(expression)?;
+    if !candidates.is_empty() && !is_interactive {
+        let names: Vec<&str> = candidates.iter().map(|program| program.get_name()).collect();
+        return Err(match names.as_slice() {
+            [name] => ErrorCode::ProgramNotFound.error(format!(
+                "'{}' is not the name of an installed program, did you mean {}? Give the full name or the index of the program to uninstall",
+                expression, name
+            )),
+            names => anyhow!(
+                "'{}' matches several programs: {}. Give the full name or the index of the one to uninstall",
+                expression,
+                names.join(", ")
+            ),
+        });
+    }
+
+    pick_program(candidates, expression, "Please select a program to uninstall:")
+}
 
-        // If multiple matches, let user choose
-        display_message(Level::Logging, "Multiple programs found:");
-        for (index, program) in program_candidates.iter().enumerate() {
-            display_tree_message(
-                1,
-                &format!("{}: {}", index + 1, program.get_name()),
-            );
+/// Uninstall the programs matching the expressions, or every installed one,
+/// after a single confirmation. Carries on past failures and returns them
+/// along with the number of programs removed.
+pub fn uninstall_programs(
+    program_manager: &ProgramManager,
+    expressions: &[String],
+    is_all: bool,
+    is_confirmed: bool,
+) -> Result<(usize, ErrorCollection), Error> {
+    let mut failures = ErrorCollection::new();
+    let mut programs: Vec<Program> = if is_all {
+        program_manager.get_installed_programs()?
+    } else {
+        Vec::new()
+    };
+
+    // `--yes` and piped input mean that nobody is there to pick between matches
+    let is_interactive: bool = !is_confirmed && std::io::stdin().is_terminal();
+    for expression in expressions {
+        match find_program_to_uninstall(program_manager, expression, is_interactive) {
+            Ok(program) if !programs.contains(&program) => programs.push(program),
+            Ok(_) => {}
+            Err(error) => failures.push(expression.clone(), error),
         }
-        let selection: usize = input_message("Please select a program to execute:")?
-            .trim()
-            .parse::<usize>()?;
+    }
+
+    if programs.is_empty() {
+        return Ok((0, failures));
+    }
 
-        if selection < 1 || selection > program_candidates.len() {
-            return Err(anyhow!("Invalid selection"));
+    let names: Vec<&str> = programs.iter().map(|program| program.get_name()).collect();
+    if !is_confirmed && !confirm(&format!("Uninstall {}?", names.join(", ")))? {
+        return Ok((0, failures));
+    }
+
+    let mut removed: usize = 0;
+    for program in &programs {
+        match program_manager.uninstall_program_by_name(program.get_name().to_string()) {
+            Ok(_) => {
+                display_tree_message(1, &format!("Removed: {}", program.get_name()));
+                removed += 1;
+            }
+            Err(error) => failures.push(program.get_name(), error),
         }
+    }
 
-        let selected_program = &program_candidates[selection - 1];
-        display_message(
-            Level::Logging,
-            &format!("Running program: {}", selected_program.get_name()),
-        );
+    Ok((removed, failures))
+}
 
-        // Execute from current working directory when using spm run
-        return execute_shell_script_with_context(
-            selected_program.get_program_path().ok_or_else(|| anyhow!("Program path not available"))?,
+/// Use the only candidate, or let the user choose one when there are several
+fn pick_program(candidates: Vec<Program>, expression: &str, prompt: &str) -> Result<Program, Error> {
+    match candidates.len() {
+        0 => Err(ErrorCode::ProgramNotFound.error(format!("No programs found with name: {}", expression))),
+        1 => Ok(candidates[0].clone()),
+        _ => {
+            display_message(Level::Logging, "Multiple programs found:");
+            let items: Vec<String> = candidates
+                .iter()
+                .map(|program| {
+                    // The origin is shown the way `spm list` shows it
+                    let origin: String = match program.get_version() {
+                        Some(version) => format!("{} @ {}", program.get_origin_label(), version),
+                        None => program.get_origin_label().to_string(),
+                    };
+                    format!("{} ({})", program.get_name(), origin)
+                })
+                .collect();
+            let selection: usize = pick_from_list(prompt, &items)?;
+
+            Ok(candidates[selection].clone())
+        }
+    }
+}
+
+/// Ask a question, where an empty answer accepts the default shown in brackets
+fn input_with_default(prompt: &str, default: &str) -> Result<String, Error> {
+    let answer: String = input_message(&format!("{} [{}]:", prompt, default))?;
+    let answer: &str = answer.trim();
+
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Walk through the details of `spm new`, offering the given values as defaults
+pub fn ask_new_program_details(arguments: &mut NewArguments) -> Result<(), Error> {
+    arguments.name = input_with_default("Program name", &arguments.name)?;
+
+    let description: String = input_message(&format!(
+        "Description [{}]:",
+        arguments.description.as_deref().unwrap_or("none")
+    ))?;
+    if !description.trim().is_empty() {
+        arguments.description = Some(description.trim().to_string());
+    }
+
+    // Ask again until the interpreter is one spm supports
+    loop {
+        let interpreter: String =
+            input_with_default("Interpreter (sh, bash, zsh, cmd)", &arguments.interpreter.to_string())?;
+        match interpreter.parse::<ShellType>() {
+            Ok(interpreter) => {
+                arguments.interpreter = interpreter;
+                return Ok(());
+            }
+            Err(error) => display_message(Level::Warn, &error.to_string()),
+        }
+    }
+}
+
+/// Buffer a script piped through stdin into a temporary file and run it
+fn execute_script_from_stdin(args: &[String]) -> Result<(), Error> {
+    if std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "`spm run -` reads the script from stdin, please pipe one in, e.g. `curl <url> | spm run -`"
+        ));
+    }
+
+    let mut content: Vec<u8> = Vec::new();
+    std::io::stdin().read_to_end(&mut content)?;
+    if content.is_empty() {
+        return Err(anyhow!("No script was received from stdin"));
+    }
+
+    let script_path: PathBuf = create_temp_directory()?.join("stdin.sh");
+    std::fs::write(&script_path, &content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    let result = Program::from_file(&script_path).and_then(|program| {
+        execute_shell_script_with_context(
+            &script_path,
+            program.get_interpreter(),
             args,
+            &program.get_environment()?,
             ExecutionContext::CurrentWorkingDirectory,
-        );
-    }
+        )
+    });
+    std::fs::remove_file(&script_path)?;
 
-    // If we get here, no programs were found
-    return Err(anyhow!("No programs found with name: {}", expression));
+    result
 }
 
 pub fn show_programs(programs: &Vec<Program>) {
@@ -122,37 +536,667 @@ pub fn show_programs(programs: &Vec<Program>) {
             index.to_string(),
             program.get_name().to_string(),
             program.get_interpreter().to_string(),
-            program.get_program_path().unwrap_or("N/A").to_string(),
+            program.get_origin_label().to_string(),
+            program.get_version().unwrap_or("-").to_string(),
+            program
+                .get_installed_at()
+                .map_or("-".to_string(), |timestamp| format_timestamp(timestamp, false)),
+            program
+                .get_program_path()
+                .map_or("N/A".to_string(), |path| path.display().to_string()),
         ]);
     }
 
-    display_form(vec!["Index", "Name", "Interpreter", "Path"], &form_data);
+    display_form(vec!["Index", "Name", "Interpreter", "Origin", "Version", "Installed", "Path"], &form_data);
 }
 
-pub fn clone_git_repository(git_url: &str, destination: &Path) -> Result<(), Error> {
+/// Format seconds since the Unix epoch as a UTC date, with the time of day if requested
+pub fn format_timestamp(timestamp: u64, is_time_included: bool) -> String {
+    // Convert the day count to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days: i64 = (timestamp / 86400) as i64 + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month: i64 = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let date: String = format!("{:04}-{:02}-{:02}", year, month, day);
+    if !is_time_included {
+        return date;
+    }
+
+    let seconds_of_day: u64 = timestamp % 86400;
+    format!(
+        "{} {:02}:{:02}:{:02} UTC",
+        date,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Show search results together with their match scores
+pub fn show_search_results(results: &[(Program, usize)]) {
+    let form_data: Vec<Vec<String>> = results
+        .iter()
+        .map(|(program, score)| {
+            vec![
+                score.to_string(),
+                program.get_name().to_string(),
+                program.get_interpreter().to_string(),
+                program.get_origin_label().to_string(),
+            ]
+        })
+        .collect();
+
+    display_form(vec!["Score", "Name", "Interpreter", "Origin"], &form_data);
+}
+
+/// Show the details of an installed program, one field per line
+pub fn show_program_info(info: &ProgramInfo) {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+
+    display_tree_output(0, &format!("Name: {}", info.name));
+    display_tree_output(0, &format!("Interpreter: {}", info.interpreter));
+    display_tree_output(0, &format!("Path: {}", quote_path(&info.path)));
+    display_tree_output(
+        0,
+        &format!("Bin entry: {}", optional(info.bin_entry.as_deref().map(quote_path))),
+    );
+    display_tree_output(0, &format!("Source: {}", optional(info.metadata.source.clone())));
+    display_tree_output(0, &format!("Mirror: {}", optional(info.metadata.mirror.clone())));
+    display_tree_output(0, &format!("Version: {}", optional(info.metadata.version.clone())));
+    display_tree_output(0, &format!("Commit: {}", optional(info.metadata.commit.clone())));
+    display_tree_output(
+        0,
+        &format!(
+            "Installed at: {}",
+            optional(info.metadata.installed_at.map(|timestamp| format_timestamp(timestamp, true)))
+        ),
+    );
+    display_tree_output(0, &format!("Forced: {}", info.metadata.is_forced));
+    display_tree_output(
+        0,
+        &format!("Default arguments: {}", optional(info.metadata.default_args.clone())),
+    );
+}
+
+/// Add or replace the crontab line that runs a program on a schedule
+pub fn schedule_program(
+    program_manager: &ProgramManager,
+    program_name: String,
+    expression: &str,
+    args: &[String],
+) -> Result<(), Error> {
+    let program: Program = program_manager.get_program_by_name(program_name)?;
+    validate_cron_expression(expression)?;
+    let spm_executable: PathBuf = std::env::current_exe()?;
+
+    if cfg!(windows) {
+        let command: String =
+            render_task_scheduler_command(expression, &spm_executable, program.get_name(), args)?;
+        display_message(
+            Level::Warn,
+            "Cron is not available on Windows. Create a scheduled task with the following command:",
+        );
+        println!("{}", command);
+        return Ok(());
+    }
+
+    let entry: String = render_schedule_entry(
+        expression,
+        &spm_executable,
+        &get_spm_root_directory()?,
+        program.get_name(),
+        args,
+    );
+    write_crontab(&add_schedule_entry(&read_crontab()?, program.get_name(), &entry))?;
+
+    display_message(
+        Level::Logging,
+        &format!("Scheduled {} with: {}", program.get_name(), expression),
+    );
+
+    Ok(())
+}
+
+/// Remove the crontab lines that spm added for a program
+pub fn unschedule_program(program_name: &str) -> Result<(), Error> {
+    let (crontab, removed) = remove_schedule_entries(&read_crontab()?, program_name);
+
+    if removed == 0 {
+        return Err(anyhow!("{} is not scheduled by spm", program_name));
+    }
+    write_crontab(&crontab)?;
+
+    display_message(
+        Level::Logging,
+        &format!("Removed the schedule of {}", program_name),
+    );
+
+    Ok(())
+}
+
+/// Show the programs that spm scheduled in the crontab
+pub fn show_schedule() -> Result<(), Error> {
+    let entries: Vec<(String, String)> = list_schedule_entries(&read_crontab()?);
+
+    if entries.is_empty() {
+        display_message(Level::Logging, "No programs are scheduled by spm.");
+        return Ok(());
+    }
+
+    for (name, line) in entries {
+        display_tree_output(0, &name);
+        display_tree_output(1, &line);
+    }
+
+    Ok(())
+}
+
+/// Show the configured registries in the order they are searched
+pub fn show_registries() -> Result<(), Error> {
+    let registries: Vec<Registry> = Configuration::load()?.registries;
+
+    if registries.is_empty() {
+        display_message(Level::Logging, "No registries are configured.");
+        return Ok(());
+    }
+
+    let form_data: Vec<Vec<String>> = registries
+        .into_iter()
+        .map(|registry| vec![registry.name, registry.url])
+        .collect();
+    display_form(vec!["Name", "Url"], &form_data);
+
+    Ok(())
+}
+
+/// Show the programs found in the registries
+pub fn show_registry_results(results: &[RegistryMatch]) {
+    let form_data: Vec<Vec<String>> = results
+        .iter()
+        .map(|result| {
+            vec![
+                result.name.clone(),
+                result.entry.get_latest_version().unwrap_or("none").to_string(),
+                result.registry.clone(),
+                result.entry.url.clone(),
+            ]
+        })
+        .collect();
+
+    display_form(vec!["Name", "Latest", "Registry", "Url"], &form_data);
+}
+
+/// Check whether a path given to spm refers to a git repository
+pub fn is_git_url(path: &str) -> bool {
+    path.starts_with("http://")
+        || path.starts_with("https://")
+        || path.starts_with("git@")
+        || path.starts_with("ssh://")
+        || path.starts_with("file://")
+}
+
+/// Check whether a source is the `owner/repository` shorthand of a repository
+/// on the base url, optionally followed by `//<directory>` and `@<ref>`.
+/// Paths that look local, such as `./x`, `/x`, `~/x` or `x.sh`, never are.
+pub fn is_git_shorthand(source: &str) -> bool {
+    let (repository, _) = split_git_reference(source.trim());
+    let (repository, _) = split_git_subdirectory(&repository);
+    let segments: Vec<&str> = repository.split('/').collect();
+
+    segments.len() == 2
+        && !repository.ends_with(".sh")
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        })
+}
+
+/// Check whether a URL points at a single script file rather than a repository
+pub fn is_single_file_url(url: &str) -> bool {
+    let is_url: bool = url.starts_with("http://")
+        || url.starts_with("https://")
+        || url.starts_with("file://");
+    let path: &str = url.split(['?', '#']).next().unwrap_or(url);
+
+    is_url && path.ends_with(".sh")
+}
+
+/// Derive a program name from the last path segment of a URL
+pub fn get_program_name_from_url(url: &str) -> Option<String> {
+    let path: &str = url.split(['?', '#']).next()?;
+    let file_name: &str = path.trim_end_matches('/').rsplit('/').next()?;
+    let name: &str = file_name.strip_suffix(".sh").unwrap_or(file_name);
+
+    if name.is_empty() || name.contains(':') {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+/// Whether spm must not use the network
+static IS_OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbid network access for the rest of the invocation
+pub fn set_offline(is_offline: bool) {
+    IS_OFFLINE.store(is_offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    IS_OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Number of attempts made to download a file before giving up
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// Wait before the second download attempt, doubled before each following one
+const DOWNLOAD_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Download a file over HTTP(S), retrying transient failures, or copy it
+/// for `file://` URLs. Proxies are taken from the usual environment variables.
+pub fn download_file(url: &str, destination: &Path) -> Result<(), Error> {
+    if let Some(path) = url.strip_prefix("file://") {
+        std::fs::copy(path, destination)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+        return Ok(());
+    }
+
+    if is_offline() {
+        return Err(anyhow!("Cannot download {} with --offline", url));
+    }
+
+    display_message(Level::Debug, &format!("Downloading {} to {}", url, destination.display()));
+    let agent: ureq::Agent = ureq::AgentBuilder::new().try_proxy_from_env(true).build();
+    let mut last_error: Option<ureq::Error> = None;
+
+    for attempt in 0..DOWNLOAD_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(DOWNLOAD_RETRY_DELAY * 2u32.pow(attempt - 1));
+        }
+
+        match agent.get(url).call() {
+            Ok(response) => {
+                let mut content: Vec<u8> = Vec::new();
+                response.into_reader().read_to_end(&mut content)?;
+                std::fs::write(destination, content)?;
+                return Ok(());
+            }
+            // Client errors will not go away by retrying
+            Err(ureq::Error::Status(code, _)) if code < 500 => {
+                return Err(anyhow!("Failed to download {}: HTTP status {}", url, code));
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(anyhow!(
+        "Failed to download {} after {} attempts: {}",
+        url,
+        DOWNLOAD_ATTEMPTS,
+        last_error.map_or("unknown error".to_string(), |error| error.to_string())
+    ))
+}
+
+/// Compute the hex encoded SHA-256 digest of a file
+pub fn compute_sha256(path: &Path) -> Result<String, Error> {
+    let digest = Sha256::digest(std::fs::read(path)?);
+
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Check whether downloaded content plausibly is a shell script and not,
+/// for example, an HTML page or a binary file.
+pub fn looks_like_shell_script(content: &[u8]) -> bool {
+    if content.contains(&0) {
+        return false;
+    }
+
+    let head: String = String::from_utf8_lossy(&content[..content.len().min(256)])
+        .trim_start()
+        .to_lowercase();
+
+    !(head.starts_with("<!doctype") || head.starts_with("<html") || head.starts_with("<?xml"))
+}
+
+/// Maximum number of modified programs listed after an install
+const MAX_LISTED_MODIFIED_PROGRAMS: usize = 5;
+
+pub fn show_install_summary(summary: &InstallSummary) {
+    if !summary.has_changes() && summary.kept.is_empty() {
+        display_message(Level::Logging, "No changes, every program is up to date.");
+        return;
+    }
+
+    display_counts(&[
+        ("Added", summary.added.len()),
+        ("Modified", summary.modified.len()),
+        ("Removed", summary.removed.len()),
+        ("Unchanged", summary.unchanged.len()),
+        ("Kept local", summary.kept.len()),
+        ("Skipped", summary.skipped.len()),
+    ]);
+
+    for name in summary.modified.iter().take(MAX_LISTED_MODIFIED_PROGRAMS) {
+        display_tree_message(1, &format!("modified: {}", name));
+    }
+    if summary.modified.len() > MAX_LISTED_MODIFIED_PROGRAMS {
+        display_tree_message(
+            1,
+            &format!(
+                "... and {} more",
+                summary.modified.len() - MAX_LISTED_MODIFIED_PROGRAMS
+            ),
+        );
+    }
+}
+
+/// Check whether a git failure is worth retrying against a mirror,
+/// that is whether it looks like a network or server problem.
+pub fn is_mirror_retryable_error(error: &Error) -> bool {
+    match error.downcast_ref::<git2::Error>() {
+        Some(error) => {
+            matches!(
+                error.class(),
+                ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssl | ErrorClass::Ssh | ErrorClass::Os
+            ) || error.code() == GitErrorCode::NotFound
+        }
+        None => false,
+    }
+}
+
+/// Fetch from each candidate url in order with `fetch` until one succeeds,
+/// moving on only when the failure is retryable. Returns the url that worked.
+pub fn try_candidate_urls<F>(candidates: &[String], mut fetch: F) -> Result<String, Error>
+where
+    F: FnMut(&str) -> Result<(), Error>,
+{
+    let mut last_error: Option<Error> = None;
+
+    for candidate in candidates {
+        match fetch(candidate) {
+            Ok(_) => return Ok(candidate.clone()),
+            Err(error) if is_mirror_retryable_error(&error) => {
+                if candidates.len() > 1 {
+                    display_message(
+                        Level::Warn,
+                        &format!("Failed to fetch from {}: {}", candidate, error),
+                    );
+                }
+                last_error = Some(error);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow!("No url to fetch from")))
+}
+
+/// The fetch depth that libgit2 understands as the whole history
+const GIT_FETCH_DEPTH_UNSHALLOW: i32 = i32::MAX;
+
+/// How much of a repository to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloneDepth<'a> {
+    /// The whole history, with every branch and tag
+    Full,
+    /// Only the last commit of the default branch
+    Latest,
+    /// Only the last commit of a tag or branch
+    Reference(&'a str),
+}
+
+/// Split the `<url>@<ref>` shorthand into the repository URL and the git
+/// reference. Only an `@` after the last path separator counts, so that the
+/// user part of `git@host:user/repo` is left alone.
+pub fn split_git_reference(path: &str) -> (String, Option<String>) {
+    match (path.rfind('@'), path.rfind('/')) {
+        (Some(at), Some(slash)) if at > slash && at + 1 < path.len() => {
+            (path[..at].to_string(), Some(path[at + 1..].to_string()))
+        }
+        _ => (path.to_string(), None),
+    }
+}
+
+/// Split the `<url>//<subdirectory>` shorthand into the repository URL and
+/// the subdirectory. The `//` following the scheme does not count.
+pub fn split_git_subdirectory(url: &str) -> (String, Option<String>) {
+    let path_start: usize = url.find("://").map_or(0, |scheme_end| scheme_end + 3);
+
+    match url[path_start..].find("//") {
+        Some(separator) => {
+            let separator: usize = path_start + separator;
+            let subdirectory: &str = url[separator + 2..].trim_matches('/');
+            (
+                url[..separator].to_string(),
+                (!subdirectory.is_empty()).then(|| subdirectory.to_string()),
+            )
+        }
+        None => (url.to_string(), None),
+    }
+}
+
+/// Where the downloads of spm are kept between installs
+pub fn get_cache_directory() -> Result<PathBuf, Error> {
+    Ok(get_spm_root_directory()?.join(DEFAULT_CACHE_FOLDER))
+}
+
+/// Remove everything spm has cached. Returns whether there was anything to remove.
+pub fn clean_cache() -> Result<bool, Error> {
+    let cache_directory: PathBuf = get_cache_directory()?;
+    if !cache_directory.exists() {
+        return Ok(false);
+    }
+
+    std::fs::remove_dir_all(&cache_directory)?;
+    Ok(true)
+}
+
+/// The cached clone of a repository, named after a digest of its URL
+fn get_git_cache_path(git_url: &str) -> Result<PathBuf, Error> {
+    let digest: String = format!("{:x}", Sha256::digest(git_url.as_bytes()));
+
+    Ok(get_cache_directory()?.join("git").join(&digest[..16]))
+}
+
+/// Find the commit a tag, branch or commit hash names in a cached repository,
+/// or the default branch when there is no reference
+fn find_git_commit<'r>(repository: &'r Repository, reference: Option<&str>) -> Result<Commit<'r>, Error> {
+    let Some(reference) = reference else {
+        return Ok(repository.head()?.peel_to_commit()?);
+    };
+
+    let object = [
+        reference.to_string(),
+        format!("refs/tags/{}", reference),
+        format!("refs/heads/{}", reference),
+    ]
+    .iter()
+    .find_map(|candidate| repository.revparse_single(candidate).ok());
+
+    match object {
+        Some(object) => Ok(object.peel_to_commit()?),
+        None => {
+            let tags = repository.tag_names(None)?;
+            let tags: Vec<&str> = tags.iter().flatten().collect();
+            Err(ErrorCode::UnknownVersion.error(format!(
+                "Unknown version '{}'. Available tags: {}",
+                reference,
+                if tags.is_empty() { "none".to_string() } else { tags.join(", ") }
+            )))
+        }
+    }
+}
+
+/// Write the files of a tag, branch or commit of a cached repository into a
+/// directory. Returns the hash of the commit that was checked out.
+pub fn checkout_git_reference(
+    cache_path: &Path,
+    reference: Option<&str>,
+    destination: &Path,
+) -> Result<String, Error> {
+    let repository = Repository::open_bare(cache_path)?;
+    let commit = find_git_commit(&repository, reference)?;
+    display_message(
+        Level::Debug,
+        &format!("Checking out {} into {}", commit.id(), destination.display()),
+    );
+
+    std::fs::create_dir_all(destination)?;
+    let mut checkout: CheckoutBuilder = get_checkout_builder();
+    // The cache is bare, so the checkout has no index to keep up to date
+    checkout.target_dir(destination).update_index(false).force();
+    let result = repository.checkout_tree(commit.as_object(), Some(&mut checkout));
+    finish_progress();
+    result?;
+
+    Ok(commit.id().to_string())
+}
+
+/// Bring the cached clone of a repository up to date with as much history as
+/// `depth` asks for, falling back to the mirrors configured for its base url.
+/// Returns the cache and the url that served it. Offline, the cache is used as it is.
+pub fn update_git_cache(git_url: &str, depth: CloneDepth) -> Result<(PathBuf, String), Error> {
+    let cache_path: PathBuf = get_git_cache_path(git_url)?;
+
+    if is_offline() {
+        if !cache_path.exists() {
+            return Err(anyhow!(
+                "{} is not in the cache. Install it once without --offline",
+                git_url
+            ));
+        }
+        return Ok((cache_path, git_url.to_string()));
+    }
+
+    let candidates: Vec<String> = Configuration::load()?.get_candidate_urls(git_url);
+    let served_by: String = try_candidate_urls(&candidates, |candidate| {
+        fetch_git_repository(candidate, &cache_path, depth)
+    })?;
+
+    Ok((cache_path, served_by))
+}
+
+/// Fetch a repository into a bare cached clone, creating it when needed
+fn fetch_git_repository(git_url: &str, cache_path: &Path, depth: CloneDepth) -> Result<(), Error> {
+    // A cache left unreadable by an interrupted fetch is started over
+    let (repository, is_new) = match Repository::open_bare(cache_path) {
+        Ok(repository) => (repository, false),
+        Err(_) => {
+            if cache_path.exists() {
+                std::fs::remove_dir_all(cache_path)?;
+            }
+            (Repository::init_bare(cache_path)?, true)
+        }
+    };
+
     // Initialize git configurations
     let auth: GitAuthenticator = GitAuthenticator::default();
     let git_config: Config = Config::open_default()?;
 
     // Initialize git options
-    let mut fetch_options = FetchOptions::new();
     let mut proxy_options = ProxyOptions::new();
     let mut remote_callbacks = RemoteCallbacks::new();
 
     // Set git up
     remote_callbacks.credentials(auth.credentials(&git_config));
+
+    // Report the transfer each time it advances by a percent
+    report_progress(ProgressEvent::new("clone", git_url).with_progress(0, 1));
+    let mut last_percent: Option<u8> = Some(0);
+    remote_callbacks.transfer_progress(|progress| {
+        let event = ProgressEvent::new("clone", git_url)
+            .with_progress(progress.received_objects(), progress.total_objects());
+        if event.percent.is_some() && event.percent != last_percent {
+            last_percent = event.percent;
+            display_progress(&format!(
+                "Receiving objects: {}% ({}/{}), {}",
+                event.percent.unwrap_or_default(),
+                progress.received_objects(),
+                progress.total_objects(),
+                format_byte_size(progress.received_bytes())
+            ));
+            report_progress(event);
+        }
+        true
+    });
     proxy_options.auto();
+    let mut fetch_options = FetchOptions::new();
     fetch_options.proxy_options(proxy_options);
     fetch_options.remote_callbacks(remote_callbacks);
+    fetch_options.download_tags(AutotagOption::None);
 
-    // Clone into the destination directory
-    RepoBuilder::new()
-        .fetch_options(fetch_options)
-        .clone(git_url, destination)?;
+    // Only a new or already shallow cache is fetched shallow, a complete one
+    // is cheaper to bring up to date as it is. The local transport of libgit2
+    // cannot fetch shallow at all.
+    let is_shallow_allowed: bool =
+        !git_url.starts_with("file://") && (is_new || repository.is_shallow());
+    match depth {
+        CloneDepth::Full if repository.is_shallow() => {
+            fetch_options.depth(GIT_FETCH_DEPTH_UNSHALLOW);
+        }
+        CloneDepth::Latest | CloneDepth::Reference(_) if is_shallow_allowed => {
+            fetch_options.depth(1);
+        }
+        _ => {}
+    }
+
+    let refspecs: Vec<String> = match depth {
+        // The tag or the branch of that name, whichever exists
+        CloneDepth::Reference(reference) => vec![
+            format!("+refs/tags/{0}:refs/tags/{0}", reference),
+            format!("+refs/heads/{0}:refs/heads/{0}", reference),
+        ],
+        CloneDepth::Full | CloneDepth::Latest => vec![
+            "+refs/heads/*:refs/heads/*".to_string(),
+            "+refs/tags/*:refs/tags/*".to_string(),
+        ],
+    };
+
+    display_message(
+        Level::Debug,
+        &format!("Fetching {} into {}", git_url, cache_path.display()),
+    );
+    let mut remote = repository.remote_anonymous(git_url)?;
+    let result = remote.fetch(&refspecs, Some(&mut fetch_options), None);
+    finish_progress();
+    result?;
+
+    // Follow the default branch of the remote
+    if let Ok(default_branch) = remote.default_branch()
+        && let Some(default_branch) = default_branch.as_str()
+    {
+        repository.set_head(default_branch)?;
+    }
 
     Ok(())
 }
 
+/// Checkout options that show how many files were written
+fn get_checkout_builder() -> CheckoutBuilder<'static> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.progress(|_, completed, total| {
+        // The checkout gets a line of its own, after the one of the transfer
+        if completed == 0 {
+            finish_progress();
+        }
+        if total > 0 {
+            display_progress(&format!("Checking out files: {}/{}", completed, total));
+        }
+    });
+
+    checkout
+}
+
 /// Checks if a given directory is in the user's PATH environment variable.
 ///
 /// This function compares the provided directory path with each directory in the PATH,
@@ -197,3 +1241,181 @@ pub fn check_bin_directory_in_path() -> Result<bool, Error> {
 
     Ok(is_directory_in_path(&bin_directory))
 }
+
+/// Detect the user's shell from the `SHELL` environment variable.
+pub fn detect_user_shell() -> Option<Shell> {
+    let shell_path: String = std::env::var("SHELL").ok()?;
+    let shell_name: &str = Path::new(&shell_path).file_name()?.to_str()?;
+
+    shell_name.parse::<Shell>().ok()
+}
+
+/// Returns where a shell conventionally loads completion scripts from,
+/// along with a manual step the user may need to take.
+pub fn get_completions_location(
+    shell: Shell,
+    home_directory: &Path,
+) -> Result<(PathBuf, Option<String>), Error> {
+    match shell {
+        Shell::Bash => Ok((
+            home_directory
+                .join(".local")
+                .join("share")
+                .join("bash-completion")
+                .join("completions")
+                .join("spm"),
+            None,
+        )),
+        Shell::Zsh => Ok((
+            home_directory.join(".zfunc").join("_spm"),
+            Some(
+                "Add `fpath=(~/.zfunc $fpath)` before `compinit` in your ~/.zshrc if it is not there yet."
+                    .to_string(),
+            ),
+        )),
+        Shell::Fish => Ok((
+            home_directory
+                .join(".config")
+                .join("fish")
+                .join("completions")
+                .join("spm.fish"),
+            None,
+        )),
+        _ => Err(anyhow!(
+            "Installing completions for {} is not supported. Use `--stdout` and place them manually.",
+            shell
+        )),
+    }
+}
+
+/// Generate the completion script of spm for a shell.
+pub fn generate_completions(shell: Shell) -> Vec<u8> {
+    let mut command = Arguments::command();
+    let mut buffer: Vec<u8> = Vec::new();
+    clap_complete::generate(shell, &mut command, "spm", &mut buffer);
+
+    buffer
+}
+
+/// Write the completion script to the conventional location of the shell,
+/// overwriting a previous one. Returns the written path and a manual step, if any.
+pub fn install_completions(shell: Shell) -> Result<(PathBuf, Option<String>), Error> {
+    let home_directory: PathBuf =
+        dirs::home_dir().ok_or_else(|| anyhow!("Failed to locate home directory"))?;
+    let (path, hint) = get_completions_location(shell, &home_directory)?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, generate_completions(shell))?;
+
+    Ok((path, hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_format_from_the_epoch() {
+        assert_eq!(format_timestamp(0, false), "1970-01-01");
+        assert_eq!(format_timestamp(0, true), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(86399, true), "1970-01-01 23:59:59 UTC");
+        assert_eq!(format_timestamp(86400, true), "1970-01-02 00:00:00 UTC");
+    }
+
+    #[test]
+    fn timestamps_format_leap_days() {
+        assert_eq!(format_timestamp(951782400, false), "2000-02-29");
+        assert_eq!(format_timestamp(1709164800, false), "2024-02-29");
+        assert_eq!(format_timestamp(1709251200, false), "2024-03-01");
+        // 2100 is not a leap year, so February ends on the 28th
+        assert_eq!(format_timestamp(4107456000, false), "2100-02-28");
+        assert_eq!(format_timestamp(4107456000 + 86400, false), "2100-03-01");
+    }
+
+    #[test]
+    fn timestamps_format_across_year_boundaries() {
+        assert_eq!(format_timestamp(1704067199, true), "2023-12-31 23:59:59 UTC");
+        assert_eq!(format_timestamp(1704067200, true), "2024-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(946684799, false), "1999-12-31");
+        assert_eq!(format_timestamp(946684800, false), "2000-01-01");
+    }
+
+    /// Builds the error a fetch from a url fails with
+    type FailureBuilder = fn(&str) -> Error;
+
+    fn network_error(message: &str) -> Error {
+        git2::Error::new(GitErrorCode::GenericError, ErrorClass::Net, message).into()
+    }
+
+    /// Try the candidates with a fetcher that fails for the given urls,
+    /// returning the outcome and the urls tried, in order
+    fn try_with_failures(
+        candidates: &[&str],
+        failures: &[(&str, FailureBuilder)],
+    ) -> (Result<String, Error>, Vec<String>) {
+        let candidates: Vec<String> = candidates.iter().map(|url| url.to_string()).collect();
+        let mut tried: Vec<String> = Vec::new();
+        let outcome = try_candidate_urls(&candidates, |url| {
+            tried.push(url.to_string());
+            match failures.iter().find(|(failing, _)| *failing == url) {
+                Some((_, error)) => Err(error(url)),
+                None => Ok(()),
+            }
+        });
+
+        (outcome, tried)
+    }
+
+    #[test]
+    fn the_first_candidate_that_works_is_used() {
+        let (outcome, tried) = try_with_failures(&["primary", "mirror-a", "mirror-b"], &[]);
+        assert_eq!(outcome.unwrap(), "primary");
+        assert_eq!(tried, vec!["primary"]);
+    }
+
+    #[test]
+    fn network_failures_fall_back_to_the_next_candidate_in_order() {
+        let (outcome, tried) = try_with_failures(
+            &["primary", "mirror-a", "mirror-b"],
+            &[("primary", network_error), ("mirror-a", network_error)],
+        );
+        assert_eq!(outcome.unwrap(), "mirror-b");
+        assert_eq!(tried, vec!["primary", "mirror-a", "mirror-b"]);
+
+        // A repository missing from a mirror is retried elsewhere as well
+        let (outcome, tried) = try_with_failures(
+            &["primary", "mirror-a"],
+            &[("primary", |_| {
+                git2::Error::new(GitErrorCode::NotFound, ErrorClass::Repository, "not found").into()
+            })],
+        );
+        assert_eq!(outcome.unwrap(), "mirror-a");
+        assert_eq!(tried, vec!["primary", "mirror-a"]);
+    }
+
+    #[test]
+    fn other_failures_stop_the_fallback() {
+        let (outcome, tried) = try_with_failures(
+            &["primary", "mirror-a"],
+            &[("primary", |_| anyhow!("The reference does not exist"))],
+        );
+        assert!(outcome.unwrap_err().to_string().contains("reference"));
+        assert_eq!(tried, vec!["primary"]);
+    }
+
+    #[test]
+    fn the_last_failure_is_returned_when_every_candidate_fails() {
+        let (outcome, tried) = try_with_failures(
+            &["primary", "mirror-a"],
+            &[("primary", network_error), ("mirror-a", network_error)],
+        );
+        assert!(outcome.unwrap_err().to_string().contains("mirror-a"));
+        assert_eq!(tried, vec!["primary", "mirror-a"]);
+
+        let (outcome, tried) = try_with_failures(&[], &[]);
+        assert!(outcome.is_err());
+        assert!(tried.is_empty());
+    }
+}
@@ -0,0 +1,2262 @@
+//! End-to-end tests running the `spm` binary against a temporary `SPM_HOME`,
+//! so that nothing touches the real home directory.
+
+use std::path::{Path, PathBuf};
+
+use assert_cmd::Command;
+use git2::{Repository, Signature};
+use tempfile::TempDir;
+
+/// An isolated spm root directory plus a workspace to run commands from
+struct Sandbox {
+    directory: TempDir,
+}
+
+/// The captured result of one spm invocation
+struct Outcome {
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Outcome {
+    fn is_success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        let directory: TempDir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(directory.path().join("workspace")).unwrap();
+        Self { directory }
+    }
+
+    fn spm_home(&self) -> PathBuf {
+        self.directory.path().join("spm-home")
+    }
+
+    fn workspace(&self) -> PathBuf {
+        self.directory.path().join("workspace")
+    }
+
+    /// Build an spm command confined to the sandbox
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spm"));
+        command
+            .args(args)
+            .env("SPM_HOME", self.spm_home())
+            .env("HOME", self.directory.path())
+            .current_dir(self.workspace());
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Outcome {
+        self.run_with_stdin(args, "")
+    }
+
+    fn run_with_stdin(&self, args: &[&str], stdin: &str) -> Outcome {
+        let mut command: Command = self.command(args);
+        command.write_stdin(stdin);
+        capture(command)
+    }
+
+    /// Write a shell script into the workspace
+    fn write_script(&self, relative_path: &str, body: &str) -> PathBuf {
+        let path: PathBuf = self.workspace().join(relative_path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        path
+    }
+
+    fn installed_program(&self, name: &str) -> PathBuf {
+        self.spm_home()
+            .join("programs")
+            .join(format!("{}.sh", name))
+    }
+}
+
+/// Run a command to completion and capture its exit code and output
+fn capture(mut command: Command) -> Outcome {
+    let output = command.output().unwrap();
+    Outcome {
+        code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    }
+}
+
+/// A local bare repository fed by a working clone, standing in for a remote
+struct GitFixture {
+    working: Repository,
+    bare_path: PathBuf,
+}
+
+impl GitFixture {
+    fn new(root: &Path) -> Self {
+        let bare_path: PathBuf = root.join("remote.git");
+        Repository::init_bare(&bare_path).unwrap();
+        let working: Repository = Repository::init(root.join("working")).unwrap();
+        working
+            .remote("origin", bare_path.to_str().unwrap())
+            .unwrap();
+        Self { working, bare_path }
+    }
+
+    fn url(&self) -> String {
+        format!("file://{}", self.bare_path.display())
+    }
+
+    fn write_script(&self, relative_path: &str, body: &str) {
+        let path: PathBuf = self.working.workdir().unwrap().join(relative_path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    }
+
+    fn remove_script(&self, relative_path: &str) {
+        std::fs::remove_file(self.working.workdir().unwrap().join(relative_path)).unwrap();
+    }
+
+    /// Commit everything in the working clone and push it to the bare repository
+    fn publish(&self) {
+        let mut index = self.working.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"].iter(), None).unwrap();
+        index.write().unwrap();
+        let tree = self.working.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("spm", "spm@example.com").unwrap();
+        let parent = self
+            .working
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        self.working
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "update",
+                &tree,
+                &parents,
+            )
+            .unwrap();
+
+        let head: String = self.working.head().unwrap().name().unwrap().to_string();
+        self.working
+            .find_remote("origin")
+            .unwrap()
+            .push(&[format!("+{}:{}", head, head)], None)
+            .unwrap();
+        Repository::open_bare(&self.bare_path)
+            .unwrap()
+            .set_head(&head)
+            .unwrap();
+    }
+
+    /// Tag the latest published commit and push the tag
+    fn tag(&self, name: &str) {
+        let head = self.working.head().unwrap().peel_to_commit().unwrap();
+        self.working
+            .tag_lightweight(name, head.as_object(), false)
+            .unwrap();
+        let reference: String = format!("refs/tags/{}", name);
+        self.working
+            .find_remote("origin")
+            .unwrap()
+            .push(&[format!("{}:{}", reference, reference)], None)
+            .unwrap();
+    }
+}
+
+#[test]
+fn new_install_list_run_uninstall() {
+    let sandbox = Sandbox::new();
+
+    assert!(sandbox.run(&["new", "hello"]).is_success());
+    assert!(sandbox.workspace().join("hello.sh").is_file());
+
+    assert!(sandbox.run(&["install", "./hello.sh"]).is_success());
+    assert!(sandbox.installed_program("hello").is_file());
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.stdout.contains("hello"));
+    // Local installs show as local rather than as the path they were installed from
+    assert!(list.stdout.contains("local"));
+    assert!(!list.stdout.contains(sandbox.workspace().to_str().unwrap()));
+
+    let run: Outcome = sandbox.run(&["run", "hello"]);
+    assert!(run.is_success());
+    assert!(run.stdout.contains("Hello from hello!"));
+
+    assert!(sandbox.run(&["uninstall", "hello", "--yes"]).is_success());
+    assert!(!sandbox.installed_program("hello").exists());
+    assert!(!sandbox.run(&["list"]).stdout.contains("hello"));
+}
+
+#[test]
+fn home_flag_overrides_spm_home() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("hello.sh", "echo \"hello from $SPM_HOME\"");
+
+    // A relative home is resolved against the working directory
+    let home: PathBuf = sandbox.workspace().join("profile");
+    let with_home = |args: &[&str]| sandbox.run(&[&["--home", "profile"], args].concat());
+
+    assert!(with_home(&["install", "./hello.sh"]).is_success());
+    assert!(home.join("programs").join("hello.sh").is_file());
+    assert!(!sandbox.spm_home().exists());
+    assert!(with_home(&["list"]).stdout.contains("hello"));
+    assert!(
+        with_home(&["run", "hello"])
+            .stdout
+            .contains(&format!("hello from {}", home.display()))
+    );
+
+    assert!(with_home(&["uninstall", "hello", "--yes"]).is_success());
+    assert!(!home.join("programs").join("hello.sh").exists());
+    assert!(!sandbox.spm_home().exists());
+
+    // So is a relative SPM_HOME
+    let with_environment = |args: &[&str]| {
+        let mut command: Command = sandbox.command(args);
+        command.env("SPM_HOME", "environment");
+        capture(command)
+    };
+    let home: PathBuf = sandbox.workspace().join("environment");
+    assert!(with_environment(&["install", "./hello.sh"]).is_success());
+    assert!(home.join("programs").join("hello.sh").is_file());
+    assert!(
+        with_environment(&["run", "hello"])
+            .stdout
+            .contains(&format!("hello from {}", home.display()))
+    );
+}
+
+#[test]
+fn malformed_metadata_is_reported_without_hiding_programs() {
+    let sandbox = Sandbox::new();
+    let broken: [(&str, &str); 3] = [
+        ("truncated", "{\"source\": \"/tmp/trunc"),
+        ("mistyped", "{\"installed_at\": \"yesterday\"}"),
+        ("empty", ""),
+    ];
+    for (name, _) in broken.iter().chain([("healthy", "")].iter()) {
+        let script: PathBuf = sandbox.write_script(&format!("{}.sh", name), "echo ok");
+        assert!(
+            sandbox
+                .run(&["install", script.to_str().unwrap()])
+                .is_success()
+        );
+    }
+    for (name, content) in broken {
+        std::fs::write(
+            sandbox
+                .spm_home()
+                .join("programs")
+                .join(format!("{}.json", name)),
+            content,
+        )
+        .unwrap();
+    }
+
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.is_success());
+    for (name, _) in broken {
+        assert!(
+            list.stderr
+                .contains(&format!("Ignoring the metadata of {}", name))
+        );
+    }
+    assert!(list.stderr.contains("line 1 column"));
+    assert!(list.stdout.contains("healthy"));
+    assert!(sandbox.run(&["run", "healthy"]).is_success());
+
+    // Programs with a broken sidecar still run, and the sidecar is reported once
+    let run: Outcome = sandbox.run(&["run", "truncated"]);
+    assert!(run.is_success());
+    assert!(run.stdout.contains("ok"));
+    assert_eq!(
+        run.stderr
+            .matches("Ignoring the metadata of truncated")
+            .count(),
+        1
+    );
+    assert!(run.stderr.contains("truncated.json"));
+
+    let info: Outcome = sandbox.run(&["info", "truncated"]);
+    assert!(info.is_success());
+    assert!(info.stdout.contains("Source: none"));
+    assert!(info.stderr.contains("truncated.json"));
+
+    // Writing the metadata again repairs it
+    assert!(
+        sandbox
+            .run(&["meta", "set", "empty", "default_args", "fast"])
+            .is_success()
+    );
+    let mistyped: PathBuf = sandbox.workspace().join("mistyped.sh");
+    assert!(
+        sandbox
+            .run(&["install", mistyped.to_str().unwrap(), "--force"])
+            .is_success()
+    );
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.stderr.contains("Ignoring the metadata of truncated"));
+    assert!(!list.stderr.contains("Ignoring the metadata of empty"));
+    assert!(!list.stderr.contains("Ignoring the metadata of mistyped"));
+}
+
+#[cfg(unix)]
+#[test]
+fn broken_links_are_skipped_when_listing() {
+    let sandbox = Sandbox::new();
+    let script: PathBuf = sandbox.write_script("healthy.sh", "echo ok");
+    assert!(
+        sandbox
+            .run(&["install", script.to_str().unwrap()])
+            .is_success()
+    );
+    std::os::unix::fs::symlink(
+        sandbox.workspace().join("gone.sh"),
+        sandbox.installed_program("ghost"),
+    )
+    .unwrap();
+
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.is_success());
+    assert!(list.stderr.contains("links to a file that does not exist"));
+    assert!(list.stdout.contains("healthy"));
+    assert!(sandbox.run(&["run", "healthy"]).is_success());
+}
+
+#[test]
+fn quiet_and_verbose_change_what_is_printed() {
+    let sandbox = Sandbox::new();
+    let script: PathBuf = sandbox.write_script("tool.sh", "echo tool ran");
+
+    let quiet: Outcome = sandbox.run(&["install", "-q", script.to_str().unwrap()]);
+    assert!(quiet.is_success());
+    assert!(quiet.stdout.is_empty());
+    assert_eq!(sandbox.run(&["run", "-q", "tool"]).stdout, "tool ran\n");
+
+    // Errors still show, on stderr
+    let missing: Outcome = sandbox.run(&["uninstall", "-q", "missing"]);
+    assert_eq!(missing.code, Some(1));
+    assert!(missing.stdout.is_empty());
+    assert!(missing.stderr.contains("missing"));
+
+    let verbose: Outcome = sandbox.run(&["run", "--verbose", "tool"]);
+    assert!(verbose.stderr.contains("Executing"));
+    assert!(!sandbox.run(&["run", "tool"]).stderr.contains("Executing"));
+}
+
+#[test]
+fn quiet_keeps_only_the_output_that_was_asked_for() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("one.sh", "echo one");
+    fixture.write_script("two.sh", "echo two");
+    fixture.publish();
+
+    let quiet = |args: &[&str]| {
+        let outcome: Outcome = sandbox.run(&[args, &["-q"]].concat());
+        assert!(outcome.is_success(), "{:?}", args);
+        outcome.stdout
+    };
+
+    assert_eq!(quiet(&["install", &fixture.url()]), "");
+    assert_eq!(quiet(&["install", &fixture.url(), "--force"]), "");
+    assert_eq!(quiet(&["update", "--all"]), "");
+
+    // Removing a program upstream and reinstalling reports nothing either
+    fixture.remove_script("two.sh");
+    fixture.publish();
+    assert_eq!(quiet(&["install", &fixture.url(), "--force"]), "");
+    assert!(!sandbox.installed_program("two").exists());
+
+    // What was asked for is still printed
+    assert!(quiet(&["info", "one"]).contains("Name: one"));
+    assert!(quiet(&["explain"]).contains("E0001"));
+
+    assert_eq!(quiet(&["uninstall", "one", "--yes"]), "");
+}
+
+#[test]
+fn colors_follow_the_color_options() {
+    let sandbox = Sandbox::new();
+    let script: PathBuf = sandbox.write_script("tool.sh", "echo tool");
+    let install = |args: &[&str]| {
+        let outcome: Outcome =
+            sandbox.run(&[&["install", "-F", script.to_str().unwrap()], args].concat());
+        assert!(outcome.is_success());
+        outcome.stdout.contains('\u{1b}')
+    };
+
+    // Piped output is plain unless colors are forced
+    assert!(!install(&[]));
+    assert!(install(&["--color", "always"]));
+    assert!(!install(&["--color", "always", "--no-color"]));
+
+    let error: Outcome = sandbox.run(&["uninstall", "missing", "--color", "always"]);
+    assert!(error.stderr.contains('\u{1b}'));
+    let mut no_color = sandbox.command(&["uninstall", "missing"]);
+    no_color.env("NO_COLOR", "1");
+    assert!(!capture(no_color).stderr.contains('\u{1b}'));
+}
+
+#[test]
+fn informational_commands_leave_the_spm_home_alone() {
+    let sandbox = Sandbox::new();
+
+    assert!(sandbox.run(&["version"]).is_success());
+    assert!(sandbox.run(&["explain"]).is_success());
+    assert!(
+        sandbox
+            .run(&["completions", "--shell", "bash"])
+            .is_success()
+    );
+    assert!(sandbox.run(&["new", "tool"]).is_success());
+    assert!(!sandbox.spm_home().exists());
+
+    assert!(sandbox.run(&["list"]).is_success());
+    assert!(sandbox.spm_home().join("programs").is_dir());
+    assert!(!sandbox.spm_home().join("bin").exists());
+}
+
+#[test]
+fn new_programs_are_strict_and_pass_check() {
+    let sandbox = Sandbox::new();
+    assert!(sandbox.run(&["new", "my tool"]).is_success());
+
+    let script: String = std::fs::read_to_string(sandbox.workspace().join("my tool.sh")).unwrap();
+    assert!(script.contains("set -eu"));
+    assert!(script.contains("main \"$@\""));
+
+    assert!(sandbox.run(&["check", "./my tool.sh"]).is_success());
+    let help: Outcome = sandbox.run(&["run", "./my tool.sh", "--", "--help"]);
+    assert!(help.is_success());
+    assert!(help.stdout.contains("Usage: my tool"));
+}
+
+#[test]
+fn new_programs_use_the_chosen_interpreter() {
+    let sandbox = Sandbox::new();
+    assert!(
+        sandbox
+            .run(&["new", "tool", "--interpreter", "bash"])
+            .is_success()
+    );
+
+    let script: String = std::fs::read_to_string(sandbox.workspace().join("tool.sh")).unwrap();
+    assert!(script.starts_with("#!/usr/bin/env bash\n"));
+    assert!(script.contains("set -euo pipefail"));
+    assert!(
+        sandbox
+            .run(&["check", "./tool.sh"])
+            .stdout
+            .contains("(bash)")
+    );
+
+    let unknown: Outcome = sandbox.run(&["new", "other", "--interpreter", "fish"]);
+    assert_eq!(unknown.code, Some(2));
+    assert!(!sandbox.workspace().join("other.sh").exists());
+}
+
+#[test]
+fn every_template_passes_check_for_every_interpreter() {
+    let sandbox = Sandbox::new();
+
+    for interpreter in ["sh", "bash", "zsh", "cmd"] {
+        for template in ["default", "minimal"] {
+            let name: String = format!("{}-{}", template, interpreter);
+            assert!(
+                sandbox
+                    .run(&[
+                        "new",
+                        &name,
+                        "--interpreter",
+                        interpreter,
+                        "--template",
+                        template
+                    ])
+                    .is_success()
+            );
+
+            let path: String = format!("./{}.sh", name);
+            let script: String = std::fs::read_to_string(sandbox.workspace().join(&path)).unwrap();
+            assert!(script.starts_with(&format!("#!/usr/bin/env {}", interpreter)));
+
+            let checked: Outcome = sandbox.run(&["check", &path]);
+            if interpreter == "cmd" {
+                // Batch files cannot be checked, which check says rather than passing them
+                assert_eq!(checked.code, Some(1));
+                assert!(checked.stderr.contains("do not support syntax checking"));
+            } else if which::which(interpreter).is_ok() {
+                assert!(checked.is_success(), "{}: {}", name, checked.stderr);
+                assert!(checked.stdout.contains("passed"));
+            }
+        }
+    }
+}
+
+#[test]
+fn new_programs_take_a_description_without_prompting_outside_a_terminal() {
+    let sandbox = Sandbox::new();
+    // Piped answers are ignored rather than read as a wizard
+    let created: Outcome = sandbox.run_with_stdin(
+        &["new", "tool", "--description", "Tidy the downloads"],
+        "renamed\nbash\n",
+    );
+    assert!(created.is_success());
+    assert!(!created.stdout.contains("Program name"));
+    assert!(!sandbox.workspace().join("renamed.sh").exists());
+
+    let script: String = std::fs::read_to_string(sandbox.workspace().join("tool.sh")).unwrap();
+    assert!(script.starts_with("#!/usr/bin/env sh\n# Tidy the downloads\n"));
+    assert!(sandbox.run(&["check", "./tool.sh"]).is_success());
+}
+
+#[test]
+fn new_programs_start_from_a_template() {
+    let sandbox = Sandbox::new();
+    assert!(
+        sandbox
+            .run(&["new", "small", "--template", "minimal", "--yes"])
+            .is_success()
+    );
+    let script: String = std::fs::read_to_string(sandbox.workspace().join("small.sh")).unwrap();
+    assert_eq!(script, "#!/usr/bin/env sh\n\necho 'Hello from small!'\n");
+
+    std::fs::write(
+        sandbox.workspace().join("template.txt"),
+        "#!/usr/bin/env {{interpreter}}\n# {{description}}\necho {{name}} is ready\n",
+    )
+    .unwrap();
+    assert!(
+        sandbox
+            .run(&[
+                "new",
+                "tool",
+                "--template",
+                "template.txt",
+                "--interpreter",
+                "bash",
+                "--description",
+                "Does things",
+            ])
+            .is_success()
+    );
+    let script: String = std::fs::read_to_string(sandbox.workspace().join("tool.sh")).unwrap();
+    assert_eq!(
+        script,
+        "#!/usr/bin/env bash\n# Does things\necho tool is ready\n"
+    );
+
+    let unknown: Outcome = sandbox.run(&["new", "other", "--template", "fancy"]);
+    assert!(!unknown.is_success());
+    assert!(unknown.stderr.contains("No template named fancy"));
+    assert!(!sandbox.workspace().join("other.sh").exists());
+}
+
+#[test]
+fn missing_interpreters_are_refused() {
+    let sandbox = Sandbox::new();
+    // cmd is never available outside Windows
+    std::fs::write(
+        sandbox.workspace().join("batch.sh"),
+        "#!/usr/bin/env cmd\necho ran anyway\n",
+    )
+    .unwrap();
+
+    let refused: Outcome = sandbox.run(&["install", "batch.sh"]);
+    assert!(!refused.is_success());
+    assert!(refused.stderr.contains("cmd is not available"));
+    assert!(refused.stderr.contains("E0007"));
+    assert!(!sandbox.installed_program("batch").exists());
+
+    let running: Outcome = sandbox.run(&["run", "./batch.sh"]);
+    assert!(!running.is_success());
+    assert!(!running.stdout.contains("ran anyway"));
+
+    assert!(
+        sandbox
+            .run(&["install", "batch.sh", "--ignore-interpreter"])
+            .is_success()
+    );
+    let ignored: Outcome = sandbox.run(&["run", "batch", "--ignore-interpreter"]);
+    assert!(ignored.is_success());
+    assert!(
+        ignored
+            .stderr
+            .contains("running the script with sh instead")
+    );
+    assert!(ignored.stdout.contains("ran anyway"));
+}
+
+#[test]
+fn spmignore_leaves_scripts_out_of_git_installs() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("tool.sh", "echo tool");
+    fixture.write_script("lib/common_helper.sh", "echo helper");
+    fixture.write_script("lib/format.sh", "echo format");
+    fixture.write_script("tests/fixture.sh", "echo fixture");
+    fixture.write_script("ci/tests/run.sh", "echo nested");
+    fixture.write_script("scripts/release.sh", "echo release");
+    std::fs::write(
+        fixture.working.workdir().unwrap().join(".spmignore"),
+        "# Not programs\ntests/\n*_helper.sh\n/scripts/release.sh\n",
+    )
+    .unwrap();
+    fixture.publish();
+
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+    for name in ["tool", "format"] {
+        assert!(sandbox.installed_program(name).exists(), "{}", name);
+    }
+    for name in ["common_helper", "fixture", "run", "release"] {
+        assert!(!sandbox.installed_program(name).exists(), "{}", name);
+    }
+}
+
+#[test]
+fn install_a_subdirectory_of_a_git_repository() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("root.sh", "echo root");
+    fixture.write_script("tools/backup/backup.sh", "echo backup v1");
+    fixture.write_script("tools/other/other.sh", "echo other");
+    fixture.publish();
+
+    assert!(
+        sandbox
+            .run(&["install", &fixture.url(), "--subdir", "tools/backup"])
+            .is_success()
+    );
+    assert!(sandbox.installed_program("backup").exists());
+    assert!(!sandbox.installed_program("root").exists());
+    assert!(!sandbox.installed_program("other").exists());
+
+    // The shorthand does the same, and the directory is remembered for updates
+    let shorthand: String = format!("{}//tools/other", fixture.url());
+    assert!(sandbox.run(&["install", &shorthand]).is_success());
+    assert!(sandbox.installed_program("other").exists());
+    assert!(!sandbox.installed_program("root").exists());
+    assert!(sandbox.run(&["list"]).stdout.contains("tools/other"));
+
+    fixture.write_script("tools/backup/backup.sh", "echo backup v2");
+    fixture.publish();
+    assert!(sandbox.run(&["update", "backup"]).is_success());
+    assert!(sandbox.run(&["run", "backup"]).stdout.contains("backup v2"));
+
+    for subdirectory in ["tools/missing", "../outside"] {
+        let missing: Outcome = sandbox.run(&["install", &fixture.url(), "--subdir", subdirectory]);
+        assert!(!missing.is_success(), "{}", subdirectory);
+        assert!(missing.stderr.contains("has no directory named"));
+    }
+}
+
+#[test]
+fn install_sources_are_told_apart() {
+    let sandbox = Sandbox::new();
+    // Typos of local paths are reported as such instead of being cloned
+    for typo in ["./typo.sh", "typo.sh", "/nowhere/typo.sh", "typo"] {
+        let missing: Outcome = sandbox.run(&["install", typo]);
+        assert!(!missing.is_success(), "{}", typo);
+        assert!(missing.stderr.contains("Path not found"), "{}", typo);
+    }
+
+    // `owner/repository` is cloned from the base url
+    let fixture = GitFixture::new(&sandbox.directory.path().join("owner"));
+    fixture.write_script("tool.sh", "echo tool");
+    fixture.publish();
+    let base_url: String = format!("file://{}", sandbox.directory.path().display());
+    assert!(
+        sandbox
+            .run(&["install", "owner/remote.git", "--base-url", &base_url])
+            .is_success()
+    );
+    assert!(sandbox.installed_program("tool").exists());
+    assert!(sandbox.run(&["list"]).stdout.contains("owner/remote.git"));
+}
+
+#[test]
+fn install_by_name_from_a_registry() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    for version in ["v1.9", "v1.10"] {
+        fixture.write_script("greet.sh", &format!("echo {}", version));
+        fixture.publish();
+        fixture.tag(version);
+    }
+    fixture.write_script("greet.sh", "echo unreleased");
+    fixture.publish();
+
+    let index: PathBuf = sandbox.workspace().join("index.json");
+    std::fs::write(
+        &index,
+        serde_json::json!({
+            "greeter": { "url": fixture.url(), "versions": ["v1.9", "v1.10"] }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let missing: Outcome = sandbox.run(&["registry", "add", "team", "missing.json"]);
+    assert!(!missing.is_success());
+    assert!(
+        sandbox
+            .run(&["registry", "add", "team", index.to_str().unwrap()])
+            .is_success()
+    );
+    assert!(sandbox.run(&["registry", "list"]).stdout.contains("team"));
+
+    let search: Outcome = sandbox.run(&["search", "greet", "--remote"]);
+    assert!(search.stdout.contains("greeter"));
+    assert!(search.stdout.contains("v1.10"));
+
+    // The latest version is installed unless one is given
+    assert!(sandbox.run(&["install", "greeter"]).is_success());
+    assert!(sandbox.run(&["run", "greet"]).stdout.contains("v1.10"));
+    assert!(sandbox.run(&["install", "greeter@v1.9", "-F"]).is_success());
+    assert!(sandbox.run(&["run", "greet"]).stdout.contains("v1.9"));
+
+    let unknown: Outcome = sandbox.run(&["install", "nothing"]);
+    assert!(!unknown.is_success());
+    assert!(
+        unknown
+            .stderr
+            .contains("No registry lists a program named nothing")
+    );
+
+    assert!(sandbox.run(&["registry", "remove", "team"]).is_success());
+    assert!(!sandbox.run(&["registry", "remove", "team"]).is_success());
+    assert!(
+        sandbox
+            .run(&["install", "nothing"])
+            .stderr
+            .contains("Path not found")
+    );
+}
+
+#[test]
+fn reinstall_requires_force() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo first");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    sandbox.write_script("tool.sh", "echo second");
+    let duplicate: Outcome = sandbox.run(&["install", "tool.sh"]);
+    assert_eq!(duplicate.code, Some(1));
+    assert!(duplicate.stderr.contains("--force"));
+
+    assert!(sandbox.run(&["install", "tool.sh", "--force"]).is_success());
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("second"));
+}
+
+#[test]
+fn uninstall_by_index_or_keyword_after_confirmation() {
+    let sandbox = Sandbox::new();
+    for name in ["backup-db", "backup-home", "deploy"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+
+    // Declining, or not answering, keeps the program
+    assert!(sandbox.run(&["uninstall", "deploy"]).is_success());
+    assert!(sandbox.installed_program("deploy").exists());
+    assert!(
+        sandbox
+            .run_with_stdin(&["uninstall", "deploy"], "n\n")
+            .is_success()
+    );
+    assert!(sandbox.installed_program("deploy").exists());
+
+    // Indexes follow the sorted listing
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.stdout.find("backup-db").unwrap() < list.stdout.find("deploy").unwrap());
+    assert!(
+        sandbox
+            .run_with_stdin(&["uninstall", "2"], "y\n")
+            .is_success()
+    );
+    assert!(!sandbox.installed_program("deploy").exists());
+
+    let out_of_range: Outcome = sandbox.run(&["uninstall", "7", "--yes"]);
+    assert_eq!(out_of_range.code, Some(1));
+    assert!(out_of_range.stderr.contains("from 0 to 1"));
+
+    // Several keyword matches are only offered in a chooser in a terminal,
+    // otherwise the program has to be named in full
+    let ambiguous: Outcome = sandbox.run_with_stdin(&["uninstall", "backup", "--yes"], "2\n");
+    assert_eq!(ambiguous.code, Some(1));
+    assert!(
+        ambiguous
+            .stderr
+            .contains("'backup' matches several programs: backup-db, backup-home")
+    );
+    assert!(sandbox.installed_program("backup-db").exists());
+    assert!(sandbox.installed_program("backup-home").exists());
+    assert!(
+        sandbox
+            .run(&["uninstall", "backup-home", "--yes"])
+            .is_success()
+    );
+    assert!(sandbox.installed_program("backup-db").exists());
+    assert!(!sandbox.installed_program("backup-home").exists());
+
+    // Even a single keyword match is not removed without asking
+    let fuzzy: Outcome = sandbox.run(&["uninstall", "backup", "--yes"]);
+    assert_eq!(fuzzy.code, Some(1));
+    assert!(fuzzy.stderr.contains("did you mean backup-db?"));
+    assert!(sandbox.installed_program("backup-db").exists());
+}
+
+#[test]
+fn uninstall_several_programs_at_once() {
+    let sandbox = Sandbox::new();
+    for name in ["one", "two", "three", "four"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+
+    // Failures are reported without stopping the other removals
+    let partial: Outcome = sandbox.run(&["uninstall", "one", "missing", "two", "--yes"]);
+    assert_eq!(partial.code, Some(1));
+    assert!(partial.stdout.contains("Removed: one"));
+    assert!(partial.stdout.contains("Removed: two"));
+    assert!(partial.stderr.contains("missing"));
+    assert!(!sandbox.installed_program("one").exists());
+    assert!(!sandbox.installed_program("two").exists());
+
+    assert!(sandbox.run(&["uninstall", "--all"]).is_success());
+    assert!(sandbox.installed_program("three").exists());
+
+    let all: Outcome = sandbox.run_with_stdin(&["uninstall", "--all"], "yes\n");
+    assert!(all.is_success());
+    assert!(all.stdout.contains("Uninstall four, three?"));
+    assert!(!sandbox.installed_program("three").exists());
+    assert!(!sandbox.installed_program("four").exists());
+}
+
+#[test]
+fn failures_exit_non_zero() {
+    let sandbox = Sandbox::new();
+
+    assert_eq!(sandbox.run(&["uninstall", "missing"]).code, Some(1));
+    assert_eq!(sandbox.run(&["run", "missing"]).code, Some(1));
+    assert_eq!(sandbox.run(&["install", "missing.sh"]).code, Some(1));
+}
+
+#[test]
+fn install_from_local_git_repository_and_reconcile() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("one.sh", "echo one");
+    fixture.write_script("tools/two.sh", "echo two");
+    fixture.write_script("tools/three.sh", "echo three");
+    fixture.publish();
+
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+    for name in ["one", "two", "three"] {
+        assert!(sandbox.installed_program(name).is_file());
+    }
+    assert!(sandbox.run(&["list"]).stdout.contains("remote"));
+
+    fixture.remove_script("tools/two.sh");
+    fixture.publish();
+
+    assert!(
+        sandbox
+            .run(&["install", &fixture.url(), "--force"])
+            .is_success()
+    );
+    assert!(sandbox.installed_program("one").is_file());
+    assert!(!sandbox.installed_program("two").exists());
+    assert!(sandbox.installed_program("three").is_file());
+
+    // Asking removes nothing, until the conflicts are all answered with yes
+    fixture.remove_script("tools/three.sh");
+    fixture.publish();
+    let skipped: Outcome =
+        sandbox.run_with_stdin(&["install", &fixture.url(), "--on-conflict", "ask"], "s\n");
+    assert!(skipped.is_success());
+    assert!(sandbox.installed_program("three").is_file());
+
+    let accepted: Outcome =
+        sandbox.run_with_stdin(&["install", &fixture.url(), "--on-conflict", "ask"], "a\n");
+    assert!(accepted.is_success());
+    assert!(accepted.stdout.contains("Removed: three"));
+    assert!(!sandbox.installed_program("three").exists());
+    fixture.write_script("tools/three.sh", "echo three");
+    fixture.publish();
+    assert!(
+        sandbox
+            .run(&["install", &fixture.url(), "--force"])
+            .is_success()
+    );
+
+    // The programs of the source are listed and removed only once confirmed
+    let unconfirmed: Outcome = sandbox.run(&["uninstall", "--from", "remote"]);
+    assert!(unconfirmed.is_success());
+    assert!(unconfirmed.stdout.contains("Uninstall one, three?"));
+    assert!(sandbox.installed_program("one").is_file());
+
+    assert!(
+        sandbox
+            .run(&["uninstall", "--from", "remote", "--yes"])
+            .is_success()
+    );
+    assert!(!sandbox.installed_program("one").exists());
+    assert!(!sandbox.installed_program("three").exists());
+}
+
+#[test]
+fn uninstall_from_a_source_needs_it_to_be_unambiguous() {
+    let sandbox = Sandbox::new();
+    let mut urls: Vec<String> = Vec::new();
+    for owner in ["alice", "bob"] {
+        let root: PathBuf = sandbox.directory.path().join(owner);
+        std::fs::create_dir_all(&root).unwrap();
+        let fixture = GitFixture::new(&root);
+        fixture.write_script(&format!("{}.sh", owner), "echo tool");
+        fixture.publish();
+        assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+        urls.push(fixture.url());
+    }
+
+    let ambiguous: Outcome = sandbox.run(&["uninstall", "--from", "remote", "--yes"]);
+    assert_eq!(ambiguous.code, Some(1));
+    assert!(ambiguous.stderr.contains("matches several sources"));
+    assert!(ambiguous.stderr.contains("alice/remote"));
+    assert!(ambiguous.stderr.contains("bob/remote"));
+    assert!(sandbox.installed_program("alice").is_file());
+    assert!(sandbox.installed_program("bob").is_file());
+
+    // A longer suffix or the exact source picks one of them
+    assert!(
+        sandbox
+            .run(&["uninstall", "--from", "alice/remote", "--yes"])
+            .is_success()
+    );
+    assert!(!sandbox.installed_program("alice").exists());
+    assert!(
+        sandbox
+            .run(&["uninstall", "--from", &urls[1], "--yes"])
+            .is_success()
+    );
+    assert!(!sandbox.installed_program("bob").exists());
+}
+
+#[test]
+fn ambiguous_run_prompts_for_a_selection() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("deploy-app.sh", "echo ran deploy-app");
+    sandbox.write_script("deploy-db.sh", "echo ran deploy-db");
+    assert!(sandbox.run(&["install", "deploy-app.sh"]).is_success());
+    assert!(sandbox.run(&["install", "deploy-db.sh"]).is_success());
+
+    let run: Outcome = sandbox.run_with_stdin(&["run", "deploy"], "2\n");
+    assert!(run.is_success());
+    assert!(run.stdout.contains("Multiple programs found"));
+    assert!(run.stdout.contains("deploy-app (local)"));
+    assert!(!run.stdout.contains(sandbox.workspace().to_str().unwrap()));
+
+    let selected: &str = run
+        .stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(">> 2: "))
+        .and_then(|item| item.split_whitespace().next())
+        .unwrap();
+    assert!(run.stdout.contains(&format!("ran {}", selected)));
+    assert_eq!(run.stdout.matches("ran deploy-").count(), 1);
+
+    let invalid: Outcome = sandbox.run_with_stdin(&["run", "deploy"], "7\n");
+    assert_eq!(invalid.code, Some(1));
+    assert!(invalid.stderr.contains("Invalid selection"));
+}
+
+#[test]
+fn relink_is_idempotent() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    assert!(sandbox.run(&["relink"]).stdout.contains("1 created"));
+    assert!(sandbox.run(&["relink"]).stdout.contains("1 unchanged"));
+
+    std::fs::remove_file(sandbox.installed_program("tool")).unwrap();
+    assert!(sandbox.run(&["relink"]).stdout.contains("1 removed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn relink_repairs_broken_links_and_leaves_other_entries_alone() {
+    let sandbox = Sandbox::new();
+    let bin: PathBuf = sandbox.spm_home().join("bin");
+    let programs: PathBuf = sandbox.spm_home().join("programs");
+    for name in ["tool", "other"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+    std::fs::create_dir_all(&bin).unwrap();
+
+    // Broken entries into the programs directory are repaired or removed
+    std::os::unix::fs::symlink(programs.join("renamed.sh"), bin.join("tool")).unwrap();
+    std::os::unix::fs::symlink(programs.join("ghost.sh"), bin.join("ghost")).unwrap();
+
+    // Entries pointing elsewhere belong to the user
+    std::os::unix::fs::symlink("/bin/sh", bin.join("other")).unwrap();
+    std::os::unix::fs::symlink("/bin/sh", bin.join("mine")).unwrap();
+
+    let relinked: Outcome = sandbox.run(&["relink"]);
+    assert!(relinked.is_success());
+    assert!(
+        relinked
+            .stdout
+            .contains("0 created, 1 updated, 1 removed, 0 unchanged")
+    );
+    assert!(relinked.stderr.contains("not created by spm"));
+    assert_eq!(
+        std::fs::read_link(bin.join("tool")).unwrap(),
+        sandbox.installed_program("tool")
+    );
+    assert!(bin.join("ghost").symlink_metadata().is_err());
+    assert_eq!(
+        std::fs::read_link(bin.join("other")).unwrap(),
+        PathBuf::from("/bin/sh")
+    );
+    assert_eq!(
+        std::fs::read_link(bin.join("mine")).unwrap(),
+        PathBuf::from("/bin/sh")
+    );
+}
+
+#[test]
+fn uninstall_removes_the_bin_entry() {
+    let sandbox = Sandbox::new();
+    for name in ["tool", "other"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+    assert!(sandbox.run(&["relink"]).stdout.contains("2 created"));
+
+    assert!(sandbox.run(&["uninstall", "tool", "--yes"]).is_success());
+    let bin: PathBuf = sandbox.spm_home().join("bin");
+    assert!(bin.join("other").symlink_metadata().is_ok());
+    assert!(bin.join("tool").symlink_metadata().is_err());
+
+    // Nothing is left for relink to clean up
+    assert!(sandbox.run(&["relink"]).stdout.contains("0 removed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn install_link_creates_the_bin_entry() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("other.sh", "echo");
+    assert!(sandbox.run(&["install", "other.sh"]).is_success());
+
+    sandbox.write_script("tool.sh", "echo");
+    let installed: Outcome = sandbox.run(&["install", "tool.sh", "--link"]);
+    assert!(installed.is_success());
+    assert!(installed.stdout.contains("1 created, 0 updated, 0 removed"));
+    let bin: PathBuf = sandbox.spm_home().join("bin");
+    assert!(bin.join("tool").symlink_metadata().is_ok());
+
+    // Only the installed program is linked
+    assert!(bin.join("other").symlink_metadata().is_err());
+
+    // A failed install links nothing
+    let missing: Outcome = sandbox.run(&["install", "missing.sh", "--link"]);
+    assert!(!missing.is_success());
+    assert!(!missing.stdout.contains("Bin entries"));
+}
+
+#[test]
+fn completions_install_to_the_shell_location() {
+    let sandbox = Sandbox::new();
+    let home: &Path = sandbox.directory.path();
+    let expectations = [
+        (
+            "bash",
+            home.join(".local/share/bash-completion/completions/spm"),
+        ),
+        ("zsh", home.join(".zfunc/_spm")),
+        ("fish", home.join(".config/fish/completions/spm.fish")),
+    ];
+
+    for (shell, path) in expectations {
+        // Running twice must simply overwrite the previous file
+        for _ in 0..2 {
+            let install: Outcome = sandbox.run(&["completions", "install", "--shell", shell]);
+            assert!(install.is_success());
+            assert!(install.stdout.contains(&path.display().to_string()));
+        }
+        let content: String = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("spm"));
+        assert_eq!(
+            content,
+            sandbox.run(&["completions", "--shell", shell]).stdout
+        );
+    }
+
+    let mut command: Command = sandbox.command(&["completions", "install"]);
+    command.env("SHELL", "/usr/bin/fish");
+    let detected: Outcome = capture(command);
+    assert!(detected.is_success());
+    assert!(detected.stdout.contains("spm.fish"));
+}
+
+#[test]
+fn conflict_policies_apply_to_a_batch_install() {
+    // (policy, stdin, how many of the two conflicts get overwritten, success)
+    let cases = [
+        ("force", "", 2, true),
+        ("skip", "", 0, true),
+        ("fail", "", 0, false),
+        ("ask", "a\n", 2, true),
+        ("ask", "s\n", 0, true),
+        ("ask", "y\nn\n", 1, true),
+    ];
+
+    for (policy, stdin, expected, is_success) in cases {
+        let sandbox = Sandbox::new();
+        for name in ["alpha", "beta"] {
+            sandbox.write_script(&format!("{}.sh", name), "echo local");
+            assert!(
+                sandbox
+                    .run(&["install", &format!("{}.sh", name)])
+                    .is_success()
+            );
+        }
+
+        let fixture = GitFixture::new(sandbox.directory.path());
+        for name in ["alpha", "beta", "gamma"] {
+            fixture.write_script(&format!("{}.sh", name), "echo upstream");
+        }
+        fixture.publish();
+
+        let install: Outcome =
+            sandbox.run_with_stdin(&["install", &fixture.url(), "--on-conflict", policy], stdin);
+        assert_eq!(
+            install.is_success(),
+            is_success,
+            "policy {} {:?}",
+            policy,
+            stdin
+        );
+        assert!(sandbox.installed_program("gamma").is_file());
+        if !is_success {
+            // Every failure is reported, not only the first one
+            assert!(install.stderr.contains("Failed to install alpha.sh"));
+            assert!(install.stderr.contains("Failed to install beta.sh"));
+        }
+
+        let overwritten: usize = ["alpha", "beta"]
+            .iter()
+            .filter(|name| {
+                std::fs::read_to_string(sandbox.installed_program(name))
+                    .unwrap()
+                    .contains("upstream")
+            })
+            .count();
+        assert_eq!(overwritten, expected, "policy {} {:?}", policy, stdin);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted_installs_leave_no_temporary_files() {
+    let sandbox = Sandbox::new();
+
+    // A server that takes the download request and never answers it
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url: String = format!("http://{}/slow.sh", listener.local_addr().unwrap());
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_spm"))
+        .args(["install", &url])
+        .env("SPM_HOME", sandbox.spm_home())
+        .env("HOME", sandbox.directory.path())
+        .env_remove("http_proxy")
+        .env_remove("HTTP_PROXY")
+        .env_remove("ALL_PROXY")
+        .current_dir(sandbox.workspace())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let _connection = listener.accept().unwrap();
+
+    let temp_root: PathBuf = sandbox.spm_home().join("tmp");
+    assert_eq!(std::fs::read_dir(&temp_root).unwrap().count(), 1);
+
+    std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+    assert_eq!(std::fs::read_dir(&temp_root).unwrap().count(), 0);
+}
+
+/// Start spm in its own process group, the way a terminal runs a foreground job,
+/// and wait until the script it runs prints `ready`
+#[cfg(unix)]
+fn spawn_until_ready(sandbox: &Sandbox, args: &[&str], stdin: &str) -> std::process::Child {
+    use std::io::{BufRead, Write};
+    use std::os::unix::process::CommandExt;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_spm"))
+        .args(args)
+        .env("SPM_HOME", sandbox.spm_home())
+        .env("HOME", sandbox.directory.path())
+        .current_dir(sandbox.workspace())
+        .process_group(0)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    while !line.contains("ready") {
+        line.clear();
+        assert!(stdout.read_line(&mut line).unwrap() > 0);
+    }
+    child
+}
+
+/// Press Ctrl-C: every process of the group receives SIGINT
+#[cfg(unix)]
+fn interrupt_group(child: &std::process::Child) {
+    std::process::Command::new("kill")
+        .args(["-INT", "--", &format!("-{}", child.id())])
+        .status()
+        .unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted_scripts_report_their_own_status() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script(
+        "trapped.sh",
+        "trap 'exit 3' INT\necho ready\nwhile :; do sleep 0.1; done\n",
+    );
+    assert!(sandbox.run(&["install", "trapped.sh"]).is_success());
+
+    let mut child = spawn_until_ready(&sandbox, &["run", "trapped"], "");
+    interrupt_group(&child);
+    assert_eq!(child.wait().unwrap().code(), Some(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn program_shells_exit_with_the_status_of_the_shell() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    let exited: Outcome = sandbox.run_with_stdin(&["run", "tool", "--shell"], "exit 7\n");
+    assert_eq!(exited.code, Some(7));
+
+    // Ctrl-C reaches the shell, and spm waits for it to exit
+    let mut child = spawn_until_ready(
+        &sandbox,
+        &["run", "tool", "--shell"],
+        "trap 'exit 5' INT\necho ready\nwhile :; do sleep 0.1; done\n",
+    );
+    interrupt_group(&child);
+    assert_eq!(child.wait().unwrap().code(), Some(5));
+}
+
+#[test]
+fn force_reinstall_reports_what_changed() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+    let identical: Outcome = sandbox.run(&["install", "tool.sh", "--force"]);
+    assert!(identical.is_success());
+    assert!(identical.stdout.contains("No changes"));
+
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("one.sh", "echo one");
+    fixture.write_script("two.sh", "echo two");
+    fixture.publish();
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+
+    let unchanged: Outcome = sandbox.run(&["install", &fixture.url(), "--force"]);
+    assert!(unchanged.stdout.contains("No changes"));
+
+    fixture.write_script("two.sh", "echo two, revised");
+    fixture.publish();
+    let modified: Outcome = sandbox.run(&["install", &fixture.url(), "--force"]);
+    assert!(modified.is_success());
+    assert!(modified.stdout.contains("modified: two"));
+    assert!(!modified.stdout.contains("modified: one"));
+}
+
+/// Serve one file over HTTP on a local port, for as many requests as needed
+fn serve_file(content: &'static str) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = std::io::Read::read(&mut stream, &mut request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                content.len(),
+                content
+            );
+            let _ = std::io::Write::write_all(&mut stream, response.as_bytes());
+        }
+    });
+
+    format!("http://{}/scripts/setup.sh", address)
+}
+
+#[test]
+fn install_single_file_from_url() {
+    let sandbox = Sandbox::new();
+    let url: String = serve_file("#!/bin/sh\necho served\n");
+
+    let mut command: Command = sandbox.command(&["install", &url]);
+    for variable in [
+        "http_proxy",
+        "HTTP_PROXY",
+        "https_proxy",
+        "HTTPS_PROXY",
+        "ALL_PROXY",
+    ] {
+        command.env_remove(variable);
+    }
+    assert!(capture(command).is_success());
+    assert!(sandbox.run(&["run", "setup"]).stdout.contains("served"));
+    assert!(sandbox.run(&["list"]).stdout.contains(&url));
+
+    // A local file url with a custom name and a pinned checksum
+    let script: PathBuf = sandbox.write_script("raw", "echo pinned");
+    let file_url: String = format!("file://{}", script.display());
+    let digest: String = {
+        use sha2::Digest;
+        let bytes = sha2::Sha256::digest(std::fs::read(&script).unwrap());
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    };
+
+    let mismatch: Outcome = sandbox.run(&[
+        "install",
+        &file_url,
+        "--single-file",
+        "--as",
+        "pinned",
+        "--sha256",
+        "00",
+    ]);
+    assert_eq!(mismatch.code, Some(1));
+    assert!(mismatch.stderr.contains("Checksum mismatch"));
+    assert!(!sandbox.installed_program("pinned").exists());
+    assert!(!sandbox.spm_home().join("tmp").join("download").exists());
+
+    let pinned: Outcome = sandbox.run(&[
+        "install",
+        &file_url,
+        "--single-file",
+        "--as",
+        "pinned",
+        "--sha256",
+        &digest,
+    ]);
+    assert!(pinned.is_success());
+    assert!(sandbox.run(&["run", "pinned"]).stdout.contains("pinned"));
+
+    // A name cannot place the script outside the programs directory
+    let escaping: Outcome =
+        sandbox.run(&["install", &file_url, "--single-file", "--as", "../escape"]);
+    assert_eq!(escaping.code, Some(1));
+    assert!(escaping.stderr.contains("cannot be used as a program name"));
+    assert!(!sandbox.spm_home().join("escape.sh").exists());
+}
+
+#[test]
+fn git_install_falls_back_to_configured_mirrors() {
+    let sandbox = Sandbox::new();
+    let root: &Path = sandbox.directory.path();
+    let fixture = GitFixture::new(&root.join("mirror-b"));
+    fixture.write_script("tool.sh", "echo mirrored");
+    fixture.publish();
+
+    // The primary and the first mirror do not have the repository
+    std::fs::create_dir_all(sandbox.spm_home()).unwrap();
+    std::fs::write(
+        sandbox.spm_home().join("config.json"),
+        format!(
+            r#"{{ "mirrors": {{ "file://{root}/primary": ["file://{root}/mirror-a", "file://{root}/mirror-b"] }} }}"#,
+            root = root.display()
+        ),
+    )
+    .unwrap();
+
+    let url: String = format!("file://{}/primary/remote.git", root.display());
+    let install: Outcome = sandbox.run(&["install", &url]);
+    assert!(install.is_success());
+
+    let mirror: String = format!("file://{}/mirror-b/remote.git", root.display());
+    assert!(
+        install
+            .stdout
+            .contains(&format!("Served by mirror: {}", mirror))
+    );
+    assert_eq!(install.stderr.matches("Failed to fetch from").count(), 2);
+
+    let metadata: String =
+        std::fs::read_to_string(sandbox.spm_home().join("programs").join("tool.json")).unwrap();
+    assert!(metadata.contains(&mirror));
+    assert!(metadata.contains(&format!("file://{}/primary/remote", root.display())));
+}
+
+#[test]
+fn force_install_preserves_local_modifications() {
+    let sandbox = Sandbox::new();
+    let install = |args: &[&str]| {
+        let mut full: Vec<&str> = vec!["install", "tool.sh", "--force"];
+        full.extend_from_slice(args);
+        sandbox.run(&full)
+    };
+    let edit_installed = |body: &str| {
+        std::fs::write(
+            sandbox.installed_program("tool"),
+            format!("#!/bin/sh\n{}\n", body),
+        )
+        .unwrap()
+    };
+    let installed = || std::fs::read_to_string(sandbox.installed_program("tool")).unwrap();
+
+    sandbox.write_script("tool.sh", "echo v1");
+    assert!(install(&[]).is_success());
+
+    // Changed upstream only: the upstream version is taken
+    sandbox.write_script("tool.sh", "echo v2");
+    assert!(install(&[]).is_success());
+    assert!(installed().contains("v2"));
+
+    // Changed locally only: the local modification is kept
+    edit_installed("echo mine");
+    let kept: Outcome = install(&[]);
+    assert!(kept.is_success());
+    assert!(kept.stdout.contains("Kept local modifications"));
+    assert!(installed().contains("mine"));
+
+    // Changed on both sides: the flags decide without asking
+    sandbox.write_script("tool.sh", "echo v3");
+    assert!(install(&["--ours"]).is_success());
+    assert!(installed().contains("mine"));
+
+    // The kept upstream version is the new base, so it is not asked about again
+    let again: Outcome = install(&[]);
+    assert!(again.is_success());
+    assert!(again.stdout.contains("Kept local modifications"));
+    assert!(installed().contains("mine"));
+
+    sandbox.write_script("tool.sh", "echo v4");
+    assert!(install(&["--theirs"]).is_success());
+    assert!(installed().contains("v4"));
+
+    // Otherwise the user is asked, and can look at the diff first
+    edit_installed("echo mine again");
+    sandbox.write_script("tool.sh", "echo v5");
+    let asked: Outcome =
+        sandbox.run_with_stdin(&["install", "tool.sh", "--force", "--no-fancy"], "d\nk\n");
+    assert!(asked.is_success());
+    assert!(asked.stdout.contains("-echo mine again"));
+    assert!(asked.stdout.contains("+echo v5"));
+    assert!(installed().contains("mine again"));
+
+    // Neither side changed: nothing happens
+    sandbox.write_script("tool.sh", "echo mine again");
+    assert!(install(&[]).is_success());
+    assert!(installed().contains("mine again"));
+}
+
+#[test]
+fn update_refreshes_programs_from_their_source() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("local.sh", "echo first");
+    assert!(sandbox.run(&["install", "local.sh"]).is_success());
+
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("one.sh", "echo one");
+    fixture.write_script("two.sh", "echo two");
+    fixture.publish();
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+
+    let update: Outcome = sandbox.run(&["update", "--all"]);
+    assert!(update.is_success());
+    assert_eq!(update.stdout.matches("up to date").count(), 3);
+
+    sandbox.write_script("local.sh", "echo second");
+    fixture.write_script("two.sh", "echo three");
+    fixture.publish();
+
+    let update: Outcome = sandbox.run(&["update", "--all"]);
+    assert!(update.is_success());
+    assert!(update.stdout.contains("local: updated"));
+    assert!(update.stdout.contains("one: up to date"));
+    assert!(update.stdout.contains("two: updated"));
+    assert!(sandbox.run(&["run", "two"]).stdout.contains("three"));
+
+    fixture.remove_script("one.sh");
+    fixture.publish();
+    assert_eq!(sandbox.run(&["update", "one"]).code, Some(1));
+    assert!(
+        sandbox
+            .run(&["update", "local"])
+            .stdout
+            .contains("up to date")
+    );
+}
+
+#[test]
+fn check_reports_syntax_errors() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("valid.sh", "if true; then echo ok; fi");
+    sandbox.write_script("broken.sh", "if true; then echo missing");
+
+    let valid: Outcome = sandbox.run(&["check", "valid.sh"]);
+    assert!(valid.is_success());
+    assert!(valid.stdout.contains("passed"));
+
+    let broken: Outcome = sandbox.run(&["check", "broken.sh"]);
+    assert_eq!(broken.code, Some(1));
+    assert!(broken.stderr.contains("failed"));
+
+    assert!(sandbox.run(&["install", "valid.sh"]).is_success());
+    assert!(sandbox.run(&["check", "valid"]).is_success());
+    assert_eq!(sandbox.run(&["check", "missing"]).code, Some(1));
+
+    // Paths with whitespace are quoted so they can be copied into a shell
+    sandbox.write_script("with space.sh", "echo ok");
+    assert!(
+        sandbox
+            .run(&["check", "with space.sh"])
+            .stdout
+            .contains("'with space.sh'")
+    );
+}
+
+#[test]
+fn search_ranks_matching_programs() {
+    let sandbox = Sandbox::new();
+    for name in ["backup-home", "backup-db", "deploy"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+
+    let search: Outcome = sandbox.run(&["search", "backup", "db"]);
+    assert!(search.is_success());
+    let db: usize = search.stdout.find("backup-db").unwrap();
+    let home: usize = search.stdout.find("backup-home").unwrap();
+    assert!(db < home);
+    assert!(!search.stdout.contains("deploy"));
+
+    let limited: Outcome = sandbox.run(&["search", "backup", "--limit", "1"]);
+    let rows: usize = limited
+        .stdout
+        .lines()
+        .filter(|line| line.contains("backup-"))
+        .count();
+    assert_eq!(rows, 1);
+
+    let empty: Outcome = sandbox.run(&["search", "nothing"]);
+    assert!(empty.is_success());
+    assert!(empty.stdout.contains("No programs matched"));
+}
+
+#[test]
+fn search_normalizes_names_and_keywords() {
+    let sandbox = Sandbox::new();
+    let names: [&str; 3] = ["MyHTTPTool", "parse_json_file", "Version2Update"];
+    for name in names {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+
+    let table: [(&str, &str); 7] = [
+        ("my-http-tool", "MyHTTPTool"),
+        ("My_HTTPTool", "MyHTTPTool"),
+        ("__my--http__", "MyHTTPTool"),
+        ("HTTP", "MyHTTPTool"),
+        ("parseJSONFile", "parse_json_file"),
+        ("json file", "parse_json_file"),
+        ("version2-update", "Version2Update"),
+    ];
+    for (keyword, expected) in table {
+        let search: Outcome = sandbox.run(&["search", keyword]);
+        for name in names {
+            assert_eq!(
+                search.stdout.contains(name),
+                name == expected,
+                "searching {:?} for {}",
+                keyword,
+                name
+            );
+        }
+    }
+
+    // Keywords made only of separators match nothing
+    let separators: Outcome = sandbox.run(&["search", "_-_"]);
+    assert!(names.iter().all(|name| !separators.stdout.contains(name)));
+}
+
+#[test]
+fn search_weights_come_from_the_configuration() {
+    let sandbox = Sandbox::new();
+    for name in ["db", "db-tools", "alpha-db"] {
+        sandbox.write_script(&format!("{}.sh", name), "echo");
+        assert!(
+            sandbox
+                .run(&["install", &format!("{}.sh", name)])
+                .is_success()
+        );
+    }
+    let position = |output: &str, name: &str| {
+        output
+            .lines()
+            .position(|line| line.contains(&format!(" {} ", name)))
+            .unwrap()
+    };
+
+    // The exact match comes first, and equal scores are ordered by name
+    let search: Outcome = sandbox.run(&["search", "db"]);
+    assert!(position(&search.stdout, "db") < position(&search.stdout, "alpha-db"));
+    assert!(position(&search.stdout, "alpha-db") < position(&search.stdout, "db-tools"));
+    assert_eq!(search.stdout.matches(" db ").count(), 1);
+
+    std::fs::write(
+        sandbox.spm_home().join("config.json"),
+        r#"{ "search": { "exact": 0 } }"#,
+    )
+    .unwrap();
+    let search: Outcome = sandbox.run(&["search", "db"]);
+    assert!(position(&search.stdout, "db-tools") < position(&search.stdout, "db"));
+}
+
+#[test]
+fn info_shows_program_details() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    let info: Outcome = sandbox.run(&["info", "tool"]);
+    assert!(info.is_success());
+    assert!(info.stdout.contains("Name: tool"));
+    assert!(info.stdout.contains("Interpreter: sh"));
+    assert!(info.stdout.contains("tool.sh"));
+
+    let json: Outcome = sandbox.run(&["info", "tool", "--json"]);
+    let value: serde_json::Value = serde_json::from_str(&json.stdout).unwrap();
+    assert_eq!(value["name"], "tool");
+    assert!(value["source"].as_str().unwrap().ends_with("tool.sh"));
+
+    assert_eq!(sandbox.run(&["info", "missing"]).code, Some(1));
+}
+
+#[test]
+fn installs_record_their_provenance() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    let json: Outcome = sandbox.run(&["info", "tool", "--json"]);
+    let value: serde_json::Value = serde_json::from_str(&json.stdout).unwrap();
+    assert!(value["installed_at"].as_u64().unwrap() > 0);
+    assert!(value.get("forced").is_none());
+
+    sandbox.write_script("tool.sh", "echo changed");
+    assert!(sandbox.run(&["install", "tool.sh", "--force"]).is_success());
+    let json: Outcome = sandbox.run(&["info", "tool", "--json"]);
+    let value: serde_json::Value = serde_json::from_str(&json.stdout).unwrap();
+    assert_eq!(value["forced"], true);
+
+    // Programs installed before receipts existed still list
+    sandbox.write_script("legacy.sh", "echo legacy");
+    assert!(sandbox.run(&["install", "legacy.sh"]).is_success());
+    std::fs::remove_file(sandbox.installed_program("legacy").with_extension("json")).unwrap();
+
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.is_success());
+    assert!(list.stdout.contains("Installed"));
+    assert!(list.stdout.contains("legacy"));
+    assert!(
+        sandbox
+            .run(&["info", "legacy"])
+            .stdout
+            .contains("Installed at: none")
+    );
+}
+
+#[test]
+fn hostile_program_names_are_neutralized() {
+    let sandbox = Sandbox::new();
+    let name: &str = "evil\u{1b}[2J\u{1b}]0;title\u{7}\nname\u{202e}";
+    sandbox.write_script(&format!("{}.sh", name), "echo evil");
+    assert!(
+        sandbox
+            .run(&["install", &format!("{}.sh", name)])
+            .is_success()
+    );
+
+    let list: Outcome = sandbox.run(&["list"]);
+    assert!(list.stdout.contains("evil\\nname\\u{202e}"));
+    assert!(!list.stdout.contains('\u{1b}'));
+    assert!(!list.stdout.contains('\u{7}'));
+    assert!(!list.stdout.contains('\u{202e}'));
+
+    let info: Outcome = sandbox.run(&["info", name]);
+    assert!(info.stdout.contains("Name: evil\\nname"));
+    assert!(!info.stdout.contains('\u{1b}'));
+}
+
+#[test]
+fn run_reads_the_script_from_stdin() {
+    let sandbox = Sandbox::new();
+
+    let run: Outcome =
+        sandbox.run_with_stdin(&["run", "-", "world"], "#!/bin/sh\necho \"hello $1\"\n");
+    assert!(run.is_success());
+    assert!(run.stdout.contains("hello world"));
+
+    let temporary: PathBuf = sandbox.spm_home().join("tmp");
+    assert_eq!(std::fs::read_dir(temporary).unwrap().count(), 0);
+
+    assert_eq!(sandbox.run(&["run", "-"]).code, Some(1));
+}
+
+#[test]
+fn default_arguments_come_before_user_arguments() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("show.sh", "for arg in \"$@\"; do echo \"[$arg]\"; done");
+    assert!(sandbox.run(&["install", "show.sh"]).is_success());
+    assert!(
+        sandbox
+            .run(&[
+                "meta",
+                "set",
+                "show",
+                "default_args",
+                "--target 's3 bucket'"
+            ])
+            .is_success()
+    );
+
+    let run: Outcome = sandbox.run(&["run", "show", "user"]);
+    assert!(run.is_success());
+    assert!(run.stdout.contains("[--target]\n[s3 bucket]\n[user]"));
+
+    // The defaults survive a reinstall of the program
+    sandbox.write_script("show.sh", "for arg in \"$@\"; do echo \"<$arg>\"; done");
+    assert!(sandbox.run(&["install", "show.sh", "--force"]).is_success());
+    assert!(sandbox.run(&["run", "show"]).stdout.contains("<s3 bucket>"));
+
+    let run: Outcome = sandbox.run(&["run", "--no-default-args", "show", "user"]);
+    assert!(!run.stdout.contains("--target"));
+    assert!(run.stdout.contains("<user>"));
+
+    assert_eq!(
+        sandbox
+            .run(&["meta", "set", "show", "default_args", "'unclosed"])
+            .code,
+        Some(1)
+    );
+    assert!(
+        sandbox
+            .run(&["meta", "unset", "show", "default_args"])
+            .is_success()
+    );
+    assert!(!sandbox.run(&["run", "show"]).stdout.contains("s3"));
+}
+
+#[test]
+fn install_git_repository_at_a_version() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("tool.sh", "echo v1");
+    fixture.publish();
+    fixture.tag("v1");
+    fixture.write_script("tool.sh", "echo v2");
+    fixture.publish();
+
+    let install: Outcome = sandbox.run(&["install", &format!("{}@v1", fixture.url())]);
+    assert!(install.is_success());
+    // The transfer progress is only drawn in a terminal
+    assert!(!install.stdout.contains("Receiving objects"));
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("v1"));
+    assert!(sandbox.run(&["list"]).stdout.contains("v1"));
+
+    let json: Outcome = sandbox.run(&["info", "tool", "--json"]);
+    let value: serde_json::Value = serde_json::from_str(&json.stdout).unwrap();
+    assert_eq!(value["commit"].as_str().unwrap().len(), 40);
+
+    // Updating keeps the program at the version it was installed at
+    assert!(
+        sandbox
+            .run(&["update", "tool"])
+            .stdout
+            .contains("up to date")
+    );
+
+    let unknown: Outcome = sandbox.run(&["install", &fixture.url(), "--version", "v9", "-F"]);
+    assert_eq!(unknown.code, Some(1));
+    assert!(unknown.stderr.contains("Available tags: v1"));
+    assert!(!sandbox.spm_home().join("tmp").join("repo").exists());
+
+    let branch: String = fixture
+        .working
+        .head()
+        .unwrap()
+        .shorthand()
+        .unwrap()
+        .to_string();
+
+    assert!(
+        sandbox
+            .run(&["install", &fixture.url(), "--version", &branch, "-F"])
+            .is_success()
+    );
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("v2"));
+
+    // A full clone lands on the requested tag as well
+    assert!(
+        sandbox
+            .run(&[
+                "install",
+                &fixture.url(),
+                "--version",
+                "v1",
+                "--full-clone",
+                "-F"
+            ])
+            .is_success()
+    );
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("v1"));
+}
+
+#[test]
+fn install_git_repository_at_an_abbreviated_commit() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    let mut commits: Vec<String> = Vec::new();
+    for version in ["v1", "v2", "v3"] {
+        fixture.write_script("tool.sh", &format!("echo {}", version));
+        fixture.publish();
+        commits.push(
+            fixture
+                .working
+                .head()
+                .unwrap()
+                .peel_to_commit()
+                .unwrap()
+                .id()
+                .to_string(),
+        );
+    }
+
+    let middle: &str = &commits[1];
+    let install: Outcome =
+        sandbox.run(&["install", &format!("{}@{}", fixture.url(), &middle[..8])]);
+    assert!(install.is_success());
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("v2"));
+
+    // The receipt records the full hash the abbreviation resolved to
+    let json: Outcome = sandbox.run(&["info", "tool", "--json"]);
+    let value: serde_json::Value = serde_json::from_str(&json.stdout).unwrap();
+    assert_eq!(value["commit"].as_str(), Some(middle));
+    assert_eq!(value["version"].as_str(), Some(&middle[..8]));
+}
+
+#[test]
+fn offline_installs_come_from_the_cache() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("tool.sh", "echo v1");
+    fixture.publish();
+    fixture.tag("v1");
+    fixture.write_script("tool.sh", "echo v2");
+    fixture.publish();
+
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+    std::fs::remove_dir_all(&fixture.bare_path).unwrap();
+
+    // Every version fetched before can be installed without the remote
+    let offline: Outcome = sandbox.run(&[
+        "install",
+        "--offline",
+        "-F",
+        &format!("{}@v1", fixture.url()),
+    ]);
+    assert!(offline.is_success());
+    assert!(sandbox.run(&["run", "tool"]).stdout.contains("v1"));
+
+    let download: Outcome = sandbox.run(&["install", "--offline", "http://127.0.0.1:9/tool.sh"]);
+    assert!(!download.is_success());
+    assert!(download.stderr.contains("--offline"));
+
+    assert!(sandbox.run(&["clean", "--cache"]).is_success());
+    let uncached: Outcome = sandbox.run(&["install", "--offline", "-F", &fixture.url()]);
+    assert!(!uncached.is_success());
+    assert!(uncached.stderr.contains("is not in the cache"));
+}
+
+#[test]
+fn concurrent_changes_wait_for_the_lock() {
+    let sandbox = Sandbox::new();
+    let script: PathBuf = sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["list"]).is_success());
+
+    // Hold the lock as another spm process would
+    let lock = std::fs::File::create(sandbox.spm_home().join("spm.lock")).unwrap();
+    lock.lock().unwrap();
+    let locked: Outcome = sandbox.run(&["install", script.to_str().unwrap()]);
+    assert!(!locked.is_success());
+    assert!(locked.stderr.contains("Another spm process is running"));
+    assert!(!sandbox.run(&["clean"]).is_success());
+    assert!(sandbox.run(&["list"]).is_success());
+
+    let holder = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        drop(lock);
+    });
+    assert!(
+        sandbox
+            .run(&["install", "--wait", script.to_str().unwrap()])
+            .is_success()
+    );
+    holder.join().unwrap();
+}
+
+#[test]
+fn clean_works_on_a_fresh_home() {
+    let sandbox = Sandbox::new();
+    assert!(!sandbox.spm_home().exists());
+
+    for args in [&["clean"][..], &["clean", "--cache"]] {
+        let clean: Outcome = sandbox.run(args);
+        assert!(clean.is_success(), "{}", clean.stderr);
+        assert!(clean.stdout.contains("Removed 0 temporary entries."));
+    }
+}
+
+#[test]
+fn temporary_files_are_kept_per_process_and_swept() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("tool.sh", "echo tool");
+    fixture.publish();
+    assert!(sandbox.run(&["install", &fixture.url()]).is_success());
+
+    let temp_root: PathBuf = sandbox.spm_home().join("tmp");
+    let entries = || std::fs::read_dir(&temp_root).unwrap().count();
+    assert_eq!(entries(), 0);
+
+    // What a crashed process left behind stays until it is old enough
+    let mut exited = std::process::Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    let crashed: PathBuf = temp_root.join(format!("{}-deadbeef", exited.id()));
+    std::fs::create_dir_all(crashed.join("repo")).unwrap();
+    assert!(sandbox.run(&["clean"]).is_success());
+    assert_eq!(entries(), 1);
+
+    // The directory of a process that is still running is never removed
+    let running: PathBuf = temp_root.join(format!("{}-cafebabe", std::process::id()));
+    std::fs::create_dir_all(&running).unwrap();
+    assert!(sandbox.run(&["clean", "--older-than", "0"]).is_success());
+    assert!(!crashed.exists());
+    assert!(running.exists());
+}
+
+#[test]
+fn scripts_run_with_their_declared_interpreter() {
+    let sandbox = Sandbox::new();
+    let script: PathBuf = sandbox.workspace().join("arrays.sh");
+    std::fs::write(
+        &script,
+        "#!/usr/bin/env bash\nitems=(first second)\n[[ -n \"${items[1]}\" ]] && echo \"got ${items[1]}\"\n",
+    )
+    .unwrap();
+
+    let run: Outcome = sandbox.run(&["run", "arrays.sh"]);
+    assert!(run.is_success());
+    assert!(run.stdout.contains("got second"));
+
+    assert!(sandbox.run(&["install", "arrays.sh"]).is_success());
+    assert!(
+        sandbox
+            .run(&["run", "arrays"])
+            .stdout
+            .contains("got second")
+    );
+}
+
+#[test]
+fn run_shell_opens_the_program_environment() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("tool.sh", "echo tool");
+    assert!(sandbox.run(&["install", "tool.sh"]).is_success());
+
+    let shell: Outcome = sandbox.run_with_stdin(
+        &["run", "tool", "--shell"],
+        "echo \"name=$SPM_PROGRAM_NAME\"\nexit 3\n",
+    );
+    assert_eq!(shell.code, Some(3));
+    assert!(shell.stdout.contains("with the environment of tool"));
+    assert!(shell.stdout.contains("name=tool"));
+    assert!(!shell.stdout.contains("Running program"));
+}
+
+#[test]
+fn run_exits_with_the_script_exit_code() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("three.sh", "exit 3");
+    assert_eq!(sandbox.run(&["run", "three.sh"]).code, Some(3));
+
+    assert!(sandbox.run(&["install", "three.sh"]).is_success());
+    assert_eq!(sandbox.run(&["run", "three"]).code, Some(3));
+
+    // A script killed by a signal exits like a shell would report it
+    sandbox.write_script("killed.sh", "kill -TERM $$");
+    assert_eq!(sandbox.run(&["run", "killed.sh"]).code, Some(128 + 15));
+}
+
+#[test]
+fn every_error_code_has_an_explanation() {
+    let sandbox = Sandbox::new();
+    let list: Outcome = sandbox.run(&["explain"]);
+    assert!(list.is_success());
+
+    let codes: Vec<String> = list
+        .stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(">> "))
+        .filter_map(|line| line.split(':').next())
+        .map(str::to_string)
+        .collect();
+    assert!(!codes.is_empty());
+
+    for code in codes {
+        let explanation: Outcome = sandbox.run(&["explain", &code.to_lowercase()]);
+        assert!(explanation.is_success(), "{}", code);
+        assert!(explanation.stdout.contains("Common fixes"), "{}", code);
+    }
+
+    assert_eq!(sandbox.run(&["explain", "E9999"]).code, Some(1));
+
+    // Failures point at their explanation
+    assert!(
+        sandbox
+            .run(&["run", "missing"])
+            .stderr
+            .contains("spm explain E0001")
+    );
+}
+
+#[test]
+fn scripts_can_locate_themselves() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script(
+        "where.sh",
+        "echo \"name=$SPM_PROGRAM_NAME\"\necho \"path=$SPM_PROGRAM_PATH\"\necho \"dir=$SPM_PROGRAM_DIR\"",
+    );
+    assert!(sandbox.run(&["install", "where.sh"]).is_success());
+
+    let installed: PathBuf = sandbox.installed_program("where").canonicalize().unwrap();
+    let run: Outcome = sandbox.run(&["run", "where"]);
+    assert!(run.stdout.contains("name=where"));
+    assert!(
+        run.stdout
+            .contains(&format!("path={}", installed.display()))
+    );
+    assert!(
+        run.stdout
+            .contains(&format!("dir={}", installed.parent().unwrap().display()))
+    );
+
+    let local: PathBuf = sandbox.workspace().canonicalize().unwrap();
+    let run: Outcome = sandbox.run(&["run", "where.sh"]);
+    assert!(run.stdout.contains(&format!("dir={}", local.display())));
+}
+
+#[cfg(unix)]
+#[test]
+fn schedule_manages_only_its_own_crontab_lines() {
+    let sandbox = Sandbox::new();
+    sandbox.write_script("backup.sh", "echo backup");
+    assert!(sandbox.run(&["install", "backup.sh"]).is_success());
+
+    // A fake crontab that keeps the table in a file
+    let fake_bin: PathBuf = sandbox.directory.path().join("fake-bin");
+    let table: PathBuf = sandbox.directory.path().join("crontab.txt");
+    std::fs::create_dir_all(&fake_bin).unwrap();
+    std::fs::write(
+        fake_bin.join("crontab"),
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = \"-l\" ]; then\n  [ -f '{0}' ] || {{ echo 'no crontab for user' >&2; exit 1; }}\n  cat '{0}'\nelse\n  cat > '{0}'\nfi\n",
+            table.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(
+        fake_bin.join("crontab"),
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )
+    .unwrap();
+    let path: String = format!("{}:{}", fake_bin.display(), std::env::var("PATH").unwrap());
+    let schedule = |args: &[&str]| {
+        let mut command: Command = sandbox.command(args);
+        command.env("PATH", &path);
+        capture(command)
+    };
+
+    assert!(
+        schedule(&["schedule", "--list"])
+            .stdout
+            .contains("No programs are scheduled")
+    );
+
+    let existing: &str =
+        "MAILTO=me@example.com\n0 1 * * * /usr/bin/true # spm-schedule-like but not ours\n";
+    std::fs::write(&table, existing).unwrap();
+
+    assert!(schedule(&["schedule", "backup", "0 3 * * *", "--target", "s3"]).is_success());
+    assert!(schedule(&["schedule", "backup", "@daily"]).is_success());
+    let content: String = std::fs::read_to_string(&table).unwrap();
+    assert!(content.starts_with(existing));
+    assert_eq!(content.matches("# spm-schedule: backup").count(), 1);
+    assert!(content.contains("@daily PATH="));
+    assert!(content.contains(" run --quiet --no-fancy backup"));
+
+    // The scheduled line runs the exact program, even when its name is the
+    // prefix of another one, and nobody is there to pick
+    sandbox.write_script("backup-db.sh", "echo backup-db");
+    assert!(sandbox.run(&["install", "backup-db.sh"]).is_success());
+    let line: &str = content
+        .lines()
+        .find(|line| line.contains("# spm-schedule: backup"))
+        .unwrap();
+    let command: &str = line
+        .strip_prefix("@daily ")
+        .and_then(|line| line.split(" # spm-schedule").next())
+        .unwrap();
+    let scheduled = std::process::Command::new("sh")
+        .args(["-c", command])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert!(scheduled.status.success());
+    assert_eq!(String::from_utf8_lossy(&scheduled.stdout).trim(), "backup");
+
+    assert!(schedule(&["schedule", "--list"]).stdout.contains("backup"));
+    assert_eq!(schedule(&["schedule", "backup", "every day"]).code, Some(1));
+    assert_eq!(schedule(&["schedule", "missing", "@daily"]).code, Some(1));
+
+    assert!(schedule(&["schedule", "--remove", "backup"]).is_success());
+    assert_eq!(std::fs::read_to_string(&table).unwrap(), existing);
+    assert_eq!(schedule(&["schedule", "--remove", "backup"]).code, Some(1));
+}
+
+#[test]
+fn progress_events_are_written_as_json_lines() {
+    let sandbox = Sandbox::new();
+    let fixture = GitFixture::new(sandbox.directory.path());
+    fixture.write_script("one.sh", "echo one");
+    fixture.write_script("two.sh", "echo two");
+    fixture.publish();
+
+    let events_path: PathBuf = sandbox.directory.path().join("events.jsonl");
+    let install: Outcome = sandbox.run(&[
+        "install",
+        &fixture.url(),
+        "--progress-file",
+        events_path.to_str().unwrap(),
+    ]);
+    assert!(install.is_success());
+    assert!(!install.stdout.contains("\"phase\""));
+
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(&events_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let mut phases: Vec<&str> = events
+        .iter()
+        .map(|event| event["phase"].as_str().unwrap())
+        .collect();
+    phases.dedup();
+    assert_eq!(phases, ["clone", "install", "done"]);
+
+    let installs: Vec<&serde_json::Value> = events
+        .iter()
+        .filter(|event| event["phase"] == "install")
+        .collect();
+    assert_eq!(installs.len(), 2);
+    assert_eq!(installs[1]["percent"], 100);
+    assert_eq!(installs[1]["message"], "installed");
+    assert_eq!(events.last().unwrap()["message"], "succeeded");
+}
+
+#[test]
+fn progress_reports_the_end_of_runs_that_fail_early() {
+    let sandbox = Sandbox::new();
+    let blocker: PathBuf = sandbox.directory.path().join("blocker");
+    std::fs::write(&blocker, "").unwrap();
+
+    // The directories of spm cannot be created under a file
+    let events_path: PathBuf = sandbox.directory.path().join("events.jsonl");
+    let install: Outcome = sandbox.run(&[
+        "--home",
+        blocker.join("home").to_str().unwrap(),
+        "--progress-file",
+        events_path.to_str().unwrap(),
+        "install",
+        "./missing.sh",
+    ]);
+    assert_eq!(install.code, Some(1));
+
+    let events: String = std::fs::read_to_string(&events_path).unwrap();
+    let last: serde_json::Value = serde_json::from_str(events.lines().last().unwrap()).unwrap();
+    assert_eq!(last["phase"], "done");
+    assert_eq!(last["message"], "failed");
+}